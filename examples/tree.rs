@@ -1,7 +1,8 @@
-use php_ls::indexer::reindex_project;
+use php_ls::{config::Config, indexer::reindex_project};
 
 fn main() -> anyhow::Result<()> {
     let root_path = "/home/eksandral/projects/php-template";
-    reindex_project(&root_path)?;
+    let config = Config::load(&root_path, None)?;
+    reindex_project(&root_path, &config)?;
     Ok(())
 }
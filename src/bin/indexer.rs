@@ -1,7 +1,7 @@
 use clap::Parser as CliParser;
 use lsp_types::Url;
+use php_ls::config::Config;
 use php_ls::db::Db;
-use php_ls::indexer::index::Indexer;
 use php_ls::{indexer::*, DB};
 use std::cell::RefCell;
 use std::error::Error;
@@ -11,11 +11,6 @@ use tokio::runtime::Runtime;
 thread_local! {
 
     pub static RT:  RefCell<Runtime> = RefCell::new(Runtime::new().unwrap());
-    pub static INDEXERS: RefCell<Vec<Box<dyn Indexer>>> = RefCell::new(vec![
-       Box::new(class_declaration::ClassDeclarationIndexer::default()),
-       //Box::new(function_declarion::FunctionDeclarationIndexer::default()),
-        //Box::new(class_reference::ClassReferenceIndexer::default()),
-    ]);
 }
 fn main() -> Result<(), Box<dyn Error>> {
     let _ = dotenv::dotenv().ok();
@@ -28,7 +23,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     let root_path = args.project_path.clone();
     // TAKE FUNCTION FROM src/indexer.rs
 
-    reindex_project(&root_path)?;
+    let mut config = Config::load(&root_path, None)?;
+    if args.database.is_some() {
+        config.database = args.database.clone();
+    }
+    reindex_project(&root_path, &config)?;
     let data = DB.with_borrow_mut(|db| {
         if let Some(db) = db {
             RT.with_borrow_mut(|rt| rt.block_on(async { db.get_all().await.unwrap() }))
@@ -49,6 +48,9 @@ struct Args {
     /// Name of the person to greet
     #[arg(short, long)]
     project_path: PathBuf,
+    /// Directory of the index or `:memory:`, the user cache directory by default
+    #[arg(long, env = "PHP_LS_DATABASE")]
+    database: Option<String>,
 }
 
 fn parse_node<'a>(
@@ -31,10 +31,14 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
         id: 1309,
         fqn: "\\stdClass".to_string(),
         description: Default::default(),
+        range: location.range,
         location,
         parameters: None,
         attributes: None,
+        value: None,
         return_type: None,
+        type_fqn: None,
+        kind: ClassRecordKind::Class,
     };
     let value = serde_json::to_value(record)?;
     log::debug!("{}", value);
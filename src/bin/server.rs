@@ -1,30 +1,59 @@
-use std::{cell::RefCell, collections::HashMap, error::Error, fmt::format, fs::read, path::Path};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::format,
+    fs::read,
+    path::Path,
+};
 
+use clap::Parser as CliParser;
 use crossbeam_channel::{select, Receiver};
 use log::{debug, warn};
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionOptions,
-    CompletionParams, CompletionResponse, DeclarationCapability, DidChangeTextDocumentParams,
-    DidOpenTextDocumentParams, DidSaveTextDocumentParams, Documentation, GotoDefinitionParams,
-    GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    CallHierarchyIncomingCallsParams, CallHierarchyOutgoingCallsParams, CallHierarchyPrepareParams,
+    CallHierarchyServerCapability, CompletionItem, CompletionItemKind, CompletionItemLabelDetails,
+    CompletionOptions, CompletionParams, CompletionResponse, DeclarationCapability,
+    DidChangeTextDocumentParams, DidChangeWorkspaceFoldersParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentSymbolParams,
+    DocumentSymbolResponse, Documentation, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverContents, HoverParams, HoverProviderCapability, ImplementationProviderCapability,
     InitializeParams, LanguageString, Location, MarkedString, MarkupContent, MarkupKind, OneOf,
-    Position, Range, ServerCapabilities, ServerInfo, SignatureHelpOptions,
-    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+    Position, Range, ReferenceParams, ServerCapabilities, ServerInfo, SignatureHelpOptions,
+    SymbolInformation, TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
+    TextDocumentSyncSaveOptions, TypeHierarchyItem, TypeHierarchyPrepareParams,
+    TypeHierarchySubtypesParams, TypeHierarchySupertypesParams, Url,
+    WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities, WorkspaceSymbolParams,
+    WorkspaceSymbolResponse,
 };
 
 use lsp_server::{Connection, ExtractError, Message, Notification, Request, RequestId, Response};
 use php_ls::{
-    debug_node, indexer::reindex_project, utils::PositionInRange, ParamsGetProjectPath, DB,
+    call_hierarchy,
+    config::Config,
+    db::{ClassRecord, ClassRecordKind, Db, DbLocation, InheritanceKind},
+    debug_node,
+    document::{self, position_to_point, Document, Documents},
+    indexer::{
+        class_reference::is_class_reference, get_class_fqn, get_namespace,
+        index_autoloaded, name_resolver::NameResolver, open_index, reindex_folder, reindex_path,
+        remove_folder,
+    },
+    inference::TypeInference,
+    outline::document_symbols,
+    utils::PositionInRange,
+    ParamsGetProjectPath, DB,
 };
 use serde::de::value;
-use tree_sitter::{Parser, Query, QueryCursor, Tree};
+use tree_sitter::{Node, Query, QueryCursor, Tree};
 use tree_sitter_php::language_php;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const NAME: &str = env!("CARGO_PKG_NAME");
+/// Clients filter the list further as the user types, so it does not have to be complete.
+const WORKSPACE_SYMBOL_LIMIT: usize = 100;
 thread_local! {
     static NS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
-    static VARS: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
 }
 
 fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
@@ -33,12 +62,13 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
     //env_logger::init();
     // Note that  we must have our logging only write out to stderr.
     log::debug!("starting generic LSP server");
+    let args = Args::parse();
     // Create the transport. Includes the stdio (stdin and stdout) versions but this could
     // also be implemented to use sockets or HTTP.
     let (connection, io_threads) = Connection::stdio();
     log::debug!("connection is created");
     // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
-    let server_capabilities = serde_json::to_value(&ServerCapabilities {
+    let mut server_capabilities = serde_json::to_value(&ServerCapabilities {
         definition_provider: Some(OneOf::Left(true)),
         declaration_provider: Some(DeclarationCapability::Simple(true)),
         completion_provider: Some(CompletionOptions {
@@ -48,9 +78,28 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
         }),
         signature_help_provider: Some(SignatureHelpOptions::default()),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        references_provider: Some(OneOf::Left(true)),
+        implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: None,
+        }),
+        text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
+            open_close: Some(true),
+            change: Some(TextDocumentSyncKind::INCREMENTAL),
+            save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+            ..Default::default()
+        })),
         ..Default::default()
     })?;
+    // lsp-types has no field for the type hierarchy capability yet
+    server_capabilities["typeHierarchyProvider"] = serde_json::Value::Bool(true);
     let server_info = serde_json::to_value(&ServerInfo {
         name: NAME.to_string(),
         version: Some(VERSION.to_string()),
@@ -66,9 +115,16 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
     connection.initialize_finish(id, initialize_data)?;
     let params: InitializeParams = serde_json::from_value(params)?;
 
-    let root_path = params.get_project_path()?;
+    let root_paths = params.get_project_paths()?;
+    // all workspace folders share the index of the first one
+    let config = load_config(&root_paths[0], params.initialization_options.as_ref());
+    let database = args.database.or(config.database);
+    open_index(&DbLocation::new(&root_paths[0], database.as_deref())?)?;
     // Reindex project
-    reindex_project(&root_path)?;
+    for root_path in root_paths.iter() {
+        let config = load_config(root_path, params.initialization_options.as_ref());
+        reindex_folder(root_path, &config)?;
+    }
     // Run main loop
     main_loop(connection, params)?;
     io_threads.join()?;
@@ -78,14 +134,32 @@ fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
     Ok(())
 }
 
+#[derive(CliParser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory of the index or `:memory:`, the user cache directory by default
+    #[arg(long, env = "PHP_LS_DATABASE")]
+    database: Option<String>,
+    /// Communicate over stdio, the only supported transport
+    #[arg(long)]
+    stdio: bool,
+}
+
+/// Reads the configuration of the folder, a broken one is reported and replaced by the defaults.
+fn load_config(root_path: &Path, initialization_options: Option<&serde_json::Value>) -> Config {
+    Config::load(root_path, initialization_options).unwrap_or_else(|e| {
+        log::error!("I cannot load the configuration of {:?}: {}", root_path, e);
+        Config::default()
+    })
+}
+
 fn main_loop(
     connection: Connection,
     params: InitializeParams,
 ) -> Result<(), Box<dyn Error + Sync + Send>> {
     let mut state = ServerState {
         params,
-        current_buffer: String::new(),
-        variables: HashMap::new(),
+        documents: Documents::default(),
         namespaces: HashMap::new(),
     };
     log::debug!("starting example main loop");
@@ -151,57 +225,222 @@ impl ServerState {
             Request { id, method, params } if method == "textDocument/hover" => {
                 let params: HoverParams = serde_json::from_value(params)?;
                 log::debug!("Hover request received {:#?}", &params);
-                let value = self.get_hover(&params.text_document_position_params.position);
+                let value = self.get_hover(
+                    &params.text_document_position_params.text_document.uri,
+                    &params.text_document_position_params.position,
+                );
                 let result = serde_json::to_value(value)?;
                 Ok(Some(Response::new_ok(id, result)))
             }
-            Request { id, method, params } if method == "textDocument/didSave" => {
-                //let params: DidSaveTextDocumentParams = serde_json::from_value(params)?;
-                //self.current_buffer = String::new();
-
-                Ok(None)
-            }
             Request { id, method, params } if method == "textDocument/completion" => {
                 log::debug!("PARAMS OF COMPL REQ: {params:?}");
                 let params: CompletionParams = serde_json::from_value(params)?;
-                let list = self.get_completions(&params.text_document_position.position);
+                let list = self.get_completions(
+                    &params.text_document_position.text_document.uri,
+                    &params.text_document_position.position,
+                );
                 let value = CompletionResponse::Array(list);
                 let result = serde_json::to_value(value)?;
                 Ok(Some(Response::new_ok(id, result)))
             }
-            Request { id, method, params } if method == "textDocument/definition" => {
+            Request { id, method, params }
+                if method == "textDocument/definition" || method == "textDocument/declaration" =>
+            {
                 log::debug!("Received go to definition request");
+                let link_support = self.supports_links(&method);
                 let mut list = vec![];
                 NS.set(HashMap::new());
                 let params: GotoDefinitionParams = serde_json::from_value(params)?;
                 let current_position = params.text_document_position_params.position;
-                let path = params
-                    .text_document_position_params
-                    .text_document
-                    .uri
-                    .path()
-                    .to_string();
-                if let Some(class_name) = detect_class_name(current_position, path) {
+                let uri = &params.text_document_position_params.text_document.uri;
+                if let Some(class_name) = self.with_document(uri, |tree, contents| {
+                    detect_constant_name(&current_position, tree, contents).or_else(|| {
+                        search_member_call_expressions(tree, contents, &current_position)
+                    })
+                }) {
                     log::debug!("I found it.. Yehoo {}", &class_name);
                     if let Ok(results) = DB.with_borrow_mut(|db| {
                         if let Some(db) = db {
                             //log::debug!("BEFORE SEARCH");
                             let r = db.find_by_fqn(&class_name);
                             //log::debug!("FROM DB {:?}", r);
+                            // the symbol can live in a file the index has not seen yet
+                            if matches!(&r, Ok(rows) if rows.is_empty())
+                                && index_autoloaded(db, &class_name)
+                            {
+                                return db.find_by_fqn(&class_name);
+                            }
                             r
                         } else {
                             Ok(vec![])
                         }
                     }) {
-                        results.iter().for_each(|row| {
-                            list.push(row.location.clone());
-                        });
+                        list = results;
                     }
                 } else {
                     log::debug!("I cannot find any symbol for definition");
                 }
-                let value = GotoDefinitionResponse::Array(list);
-                let result = serde_json::to_value(value)?;
+                let result = serde_json::to_value(goto_response(list, link_support))?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "textDocument/implementation" => {
+                let link_support = self.supports_links(&method);
+                let params: GotoDefinitionParams = serde_json::from_value(params)?;
+                let position = params.text_document_position_params.position;
+                let uri = &params.text_document_position_params.text_document.uri;
+                let list = self
+                    .with_document(uri, |tree, contents| {
+                        detect_reference_name(position, tree, contents)
+                    })
+                    .map_or(vec![], |fqn| {
+                        log::debug!("Searching for implementations of {}", &fqn);
+                        DB.with_borrow_mut(|db| {
+                            db.as_mut().map_or(vec![], |db| {
+                                db.find_implementations(&fqn).unwrap_or_default()
+                            })
+                        })
+                    });
+                let result = serde_json::to_value(goto_response(list, link_support))?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "textDocument/prepareTypeHierarchy" => {
+                let params: TypeHierarchyPrepareParams = serde_json::from_value(params)?;
+                let position = params.text_document_position_params.position;
+                let uri = &params.text_document_position_params.text_document.uri;
+                let items: Vec<TypeHierarchyItem> = self
+                    .with_document(uri, |tree, contents| {
+                        detect_reference_name(position, tree, contents)
+                    })
+                    .map_or(vec![], |fqn| {
+                        DB.with_borrow_mut(|db| {
+                            db.as_mut()
+                                .map_or(vec![], |db| db.find_by_fqn(&fqn).unwrap_or_default())
+                        })
+                    })
+                    .iter()
+                    .filter(|x| x.kind.is_class_like())
+                    .map(type_hierarchy_item)
+                    .collect();
+                let result = serde_json::to_value(items)?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params }
+                if method == "typeHierarchy/supertypes" || method == "typeHierarchy/subtypes" =>
+            {
+                let supertypes = method == "typeHierarchy/supertypes";
+                let item = if supertypes {
+                    serde_json::from_value::<TypeHierarchySupertypesParams>(params)?.item
+                } else {
+                    serde_json::from_value::<TypeHierarchySubtypesParams>(params)?.item
+                };
+                // the prepare request puts FQN of the type into the item
+                let items = match item.data.as_ref().and_then(|data| data.as_str()) {
+                    Some(fqn) => related_types(fqn, supertypes),
+                    None => vec![],
+                };
+                let result = serde_json::to_value(items)?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "textDocument/prepareCallHierarchy" => {
+                let params: CallHierarchyPrepareParams = serde_json::from_value(params)?;
+                let position = params.text_document_position_params.position;
+                let uri = &params.text_document_position_params.text_document.uri;
+                let items = self
+                    .with_document(uri, |tree, contents| {
+                        detect_reference_name(position, tree, contents)
+                    })
+                    .map_or(vec![], |fqn| {
+                        DB.with_borrow_mut(|db| {
+                            db.as_mut().map_or(vec![], |db| {
+                                call_hierarchy::prepare(db, &fqn).unwrap_or_default()
+                            })
+                        })
+                    });
+                let result = serde_json::to_value(items)?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "callHierarchy/incomingCalls" => {
+                let params: CallHierarchyIncomingCallsParams = serde_json::from_value(params)?;
+                let calls = match call_hierarchy::item_fqn(&params.item) {
+                    Some(fqn) => DB.with_borrow_mut(|db| {
+                        db.as_mut().map_or(vec![], |db| {
+                            call_hierarchy::incoming_calls(db, fqn).unwrap_or_default()
+                        })
+                    }),
+                    None => vec![],
+                };
+                let result = serde_json::to_value(calls)?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "callHierarchy/outgoingCalls" => {
+                let params: CallHierarchyOutgoingCallsParams = serde_json::from_value(params)?;
+                let calls = DB.with_borrow_mut(|db| {
+                    db.as_mut().map_or(vec![], |db| {
+                        call_hierarchy::outgoing_calls(db, &params.item).unwrap_or_default()
+                    })
+                });
+                let result = serde_json::to_value(calls)?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "textDocument/documentSymbol" => {
+                let params: DocumentSymbolParams = serde_json::from_value(params)?;
+                let symbols = self
+                    .with_document(&params.text_document.uri, |tree, contents| {
+                        Some(document_symbols(contents, tree, &params.text_document.uri))
+                    })
+                    .unwrap_or_default();
+                let result = serde_json::to_value(DocumentSymbolResponse::Nested(symbols))?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "workspace/symbol" => {
+                let params: WorkspaceSymbolParams = serde_json::from_value(params)?;
+                let results = DB
+                    .with_borrow_mut(|db| match db {
+                        Some(db) => db
+                            .search_symbols(&params.query, WORKSPACE_SYMBOL_LIMIT)
+                            .ok(),
+                        None => None,
+                    })
+                    .unwrap_or_default();
+                #[allow(deprecated)]
+                let symbols: Vec<SymbolInformation> = results
+                    .iter()
+                    .map(|row| SymbolInformation {
+                        name: row.name().to_string(),
+                        kind: row.kind.symbol_kind(),
+                        tags: None,
+                        deprecated: None,
+                        location: row.location.clone(),
+                        container_name: row.container_name().map(|x| x.to_string()),
+                    })
+                    .collect();
+                let result = serde_json::to_value(WorkspaceSymbolResponse::Flat(symbols))?;
+                Ok(Some(Response::new_ok(id, result)))
+            }
+            Request { id, method, params } if method == "textDocument/references" => {
+                log::debug!("Received references request");
+                let params: ReferenceParams = serde_json::from_value(params)?;
+                let current_position = params.text_document_position.position;
+                let uri = &params.text_document_position.text_document.uri;
+                let mut list = vec![];
+                if let Some(fqn) = self.with_document(uri, |tree, contents| {
+                    detect_reference_name(current_position, tree, contents)
+                }) {
+                    log::debug!("Searching for references of {}", &fqn);
+                    DB.with_borrow_mut(|db| {
+                        if let Some(db) = db {
+                            if params.context.include_declaration {
+                                if let Ok(results) = db.find_by_fqn(&fqn) {
+                                    list.extend(results.into_iter().map(|row| row.location));
+                                }
+                            }
+                            if let Ok(results) = db.find_hierarchy_references(&fqn) {
+                                list.extend(results.into_iter().map(|row| row.location));
+                            }
+                        }
+                    });
+                }
+                let result = serde_json::to_value(list)?;
                 Ok(Some(Response::new_ok(id, result)))
             }
 
@@ -217,18 +456,63 @@ impl ServerState {
         match not {
             Notification { method, params } if method == "textDocument/didChange" => {
                 let params: DidChangeTextDocumentParams = serde_json::from_value(params)?;
-                let text: String = params
-                    .content_changes
-                    .get(0)
-                    .map_or("".to_string(), |x| x.text.clone());
-                self.current_buffer = text;
-                self.index_current_buffer();
+                self.documents.change(
+                    params.text_document.uri,
+                    &params.content_changes,
+                    params.text_document.version,
+                );
+            }
+            Notification { method, params } if method == "textDocument/didSave" => {
+                let params: DidSaveTextDocumentParams = serde_json::from_value(params)?;
+                let path = params
+                    .text_document
+                    .uri
+                    .to_file_path()
+                    .map_err(|_| anyhow::anyhow!("Invalid file URI {}", params.text_document.uri))?;
+                log::debug!("Reindexing saved file {:?}", &path);
+                DB.with_borrow_mut(|db| {
+                    if let Some(db) = db {
+                        if let Err(e) = reindex_path(db, &path) {
+                            log::error!("I cannot reindex {:?}: {}", &path, e);
+                        }
+                    }
+                });
             }
             Notification { method, params } if method == "textDocument/didOpen" => {
                 let params: DidOpenTextDocumentParams = serde_json::from_value(params)?;
-                let text: String = params.text_document.text;
-                self.current_buffer = text;
-                self.index_current_buffer();
+                self.documents.open(
+                    params.text_document.uri,
+                    params.text_document.text,
+                    params.text_document.version,
+                );
+            }
+            Notification { method, params } if method == "workspace/didChangeWorkspaceFolders" => {
+                let params: DidChangeWorkspaceFoldersParams = serde_json::from_value(params)?;
+                for folder in params.event.removed {
+                    let path = folder
+                        .uri
+                        .to_file_path()
+                        .map_err(|_| anyhow::anyhow!("Invalid folder URI {}", folder.uri))?;
+                    DB.with_borrow_mut(|db| {
+                        if let Some(db) = db {
+                            if let Err(e) = remove_folder(db, &path) {
+                                log::error!("I cannot remove folder {:?}: {}", &path, e);
+                            }
+                        }
+                    });
+                }
+                for folder in params.event.added {
+                    let path = folder
+                        .uri
+                        .to_file_path()
+                        .map_err(|_| anyhow::anyhow!("Invalid folder URI {}", folder.uri))?;
+                    let config = load_config(&path, self.params.initialization_options.as_ref());
+                    reindex_folder(&path, &config)?;
+                }
+            }
+            Notification { method, params } if method == "textDocument/didClose" => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(params)?;
+                self.documents.close(&params.text_document.uri);
             }
             _ => {}
         }
@@ -238,18 +522,24 @@ impl ServerState {
         log::debug!("git respose :{resp:?}");
         Ok(None)
     }
-    fn get_hover(&mut self, position: &Position) -> Option<Hover> {
-        self.index_current_buffer();
-        log::debug!("vars {:?}", self.variables);
+    fn get_hover(&mut self, uri: &Url, position: &Position) -> Option<Hover> {
+        self.index_buffer(uri);
         log::debug!("namespaces {:?}", self.namespaces);
-        log::debug!("buffer= {:?}", self.current_buffer);
-        let tree = get_parsed_tree(self.current_buffer.as_bytes()).unwrap();
-        let contents = self.current_buffer.as_bytes();
+        let document = self.documents.get(uri)?;
+        let tree = &document.tree;
+        let contents = document.as_bytes();
         let root_node = tree.root_node();
+        if let Some(fqn) = detect_constant_name(position, tree, contents) {
+            return DB
+                .with_borrow_mut(|db| db.as_mut()?.find_one_by_fqn(&fqn).ok())
+                .map(|x| record_hover(&x));
+        }
         let queries =
             vec![
-                "(member_call_expression object:(variable_name (name) @variable_name) name: (name) @method_name) @root",
+                "[(member_call_expression object: (_) @object name: (name) @method_name) (nullsafe_member_call_expression object: (_) @object name: (name) @method_name)] @root",
                 "(object_creation_expression (name) @class_name)",
+                "(function_call_expression function: [(name) (qualified_name)] @function_name)",
+                "[(member_access_expression object: (_) @object name: (name) @property_name) (nullsafe_member_access_expression object: (_) @object name: (name) @property_name)] @root",
             ];
         for (idx, query) in queries.iter().enumerate() {
             //let query = query.join(" \n");
@@ -262,51 +552,12 @@ impl ServerState {
                         if !m.captures[2].node.range().includes(position) {
                             continue;
                         }
-                        // here we need to find a type of a variable
-                        let var_name = m.captures[1].node.utf8_text(contents).unwrap();
+                        // the object can be a variable or a chain of calls
                         let method_name = m.captures[2].node.utf8_text(contents).unwrap();
-                        if let Some(var_type) = self.variables.get(var_name) {
-                            if let Some(results) = DB.with_borrow_mut(|db| {
-                                if let Some(db) = db {
-                                    let results = db
-                                        .find_by_fqn_like(
-                                            format!("{}::{}", var_type, method_name).as_str(),
-                                        )
-                                        .unwrap();
-                                    Some(results)
-                                } else {
-                                    None
-                                }
-                            }) {
-                                //process db result
-                                let hovers: Vec<Hover> = results
-                                    .iter()
-                                    .map(|x| {
-                                        let method_signature = format!(
-                                            "<?php function {}{}{} ?>\n\n---",
-                                            method_name,
-                                            x.parameters.as_ref().unwrap_or(&"()".to_string()),
-                                            x.return_type
-                                                .as_ref()
-                                                .map_or("".to_string(), |rt| format!(": {}", rt))
-                                        );
-                                        let contents = HoverContents::Array(vec![
-                                            MarkedString::LanguageString(LanguageString {
-                                                value: method_signature,
-                                                language: "php".to_string(),
-                                            }),
-                                            MarkedString::String(x.description.clone()),
-                                        ]);
-
-                                        Hover {
-                                            contents,
-                                            range: None,
-                                        }
-                                    })
-                                    .collect();
-                                return hovers.first().map(|x| x.clone());
-                            }
-                        };
+                        let types = TypeInference::new(tree, contents)
+                            .expression_types(&m.captures[1].node);
+                        return find_member_of(&types, method_name)
+                            .map(|(x, class_fqn)| member_hover(&x, &class_fqn));
                     }
                 }
                 1 => {
@@ -325,14 +576,9 @@ impl ServerState {
                                         .map(|db| db.find_one_by_fqn(class_fqn).ok())
                                         .flatten()
                                         .map(|x| {
-                                            let (ns_name,class_name) = &class_fqn.rsplit_once("\\").unwrap_or(("",""));
-                                            let class_signature = format!(
-                                                "<?php\nnamespace {}\nclass {}\n---",
-                                                ns_name, class_name
-                                            );
                                             let contents = HoverContents::Array(vec![
                                                 MarkedString::LanguageString(LanguageString {
-                                                    value: class_signature,
+                                                    value: x.signature(),
                                                     language: "php".to_string(),
                                                 }),
                                                 MarkedString::String(x.description.clone()),
@@ -348,54 +594,113 @@ impl ServerState {
                             .flatten();
                     }
                 }
+                2 => {
+                    for m in matches {
+                        let function_name_node = m.captures[0].node;
+                        if !function_name_node.range().includes(position) {
+                            continue;
+                        }
+                        let function_name = function_name_node.utf8_text(contents).unwrap();
+                        let fqn = resolve_function_fqn(
+                            function_name,
+                            &function_name_node,
+                            tree,
+                            contents,
+                        );
+                        return DB.with_borrow_mut(|db| {
+                            db.as_mut()
+                                .map(|db| db.find_one_by_fqn(&fqn).ok())
+                                .flatten()
+                                .map(|x| {
+                                    let contents = HoverContents::Array(vec![
+                                        MarkedString::LanguageString(LanguageString {
+                                            value: x.signature(),
+                                            language: "php".to_string(),
+                                        }),
+                                        MarkedString::String(x.description.clone()),
+                                    ]);
+
+                                    Hover {
+                                        contents,
+                                        range: None,
+                                    }
+                                })
+                        });
+                    }
+                }
+                3 => {
+                    for m in matches {
+                        if !m.captures[2].node.range().includes(position) {
+                            continue;
+                        }
+                        let property_name = m.captures[2].node.utf8_text(contents).unwrap();
+                        let types = TypeInference::new(tree, contents)
+                            .expression_types(&m.captures[1].node);
+                        // properties are stored with the `$` to keep them apart from methods
+                        let name = format!("${}", property_name);
+                        return find_member_of(&types, &name)
+                            .map(|(x, class_fqn)| member_hover(&x, &class_fqn));
+                    }
+                }
                 _ => (),
             }
         }
         // if nothing found we return no hover
         None
     }
-    fn get_completions(&mut self, position: &Position) -> Vec<CompletionItem> {
-        self.index_current_buffer();
-        log::debug!("vars {:?}", self.variables);
+    fn get_completions(&mut self, uri: &Url, position: &Position) -> Vec<CompletionItem> {
+        self.index_buffer(uri);
         log::debug!("namespaces {:?}", self.namespaces);
-        log::debug!("buffer= {:?}", self.current_buffer);
-        let tree = get_parsed_tree(self.current_buffer.as_bytes()).unwrap();
-        let contents = self.current_buffer.as_bytes();
+        let document = if let Some(document) = self.documents.get(uri) {
+            document
+        } else {
+            return vec![];
+        };
+        if let Some(items) = scoped_completions(document, uri, position) {
+            return items;
+        }
+        let tree = &document.tree;
+        let contents = document.as_bytes();
         let root_node = tree.root_node();
-        let queries =
-            vec!["(member_access_expression object:(variable_name (name) @variable_name)) @root"];
+        let queries = vec![
+            "[(member_access_expression object: (_) @object) (nullsafe_member_access_expression object: (_) @object)] @root",
+        ];
         for (idx, query) in queries.iter().enumerate() {
             //let query = query.join(" \n");
             let query = Query::new(language_php(), &query).unwrap();
             let mut query_cursor = QueryCursor::new();
             let matches = query_cursor.matches(&query, root_node, &contents[..]);
 
-            for m in matches {
+            // chained accesses match too, the innermost one around the cursor is completed
+            if let Some(m) = matches
+                .filter(|m| m.captures[0].node.range().includes(position))
+                .last()
+            {
                 log::debug!("MATCH {:?}", m);
-                if !m.captures[0].node.range().includes(position) {
-                    continue;
-                }
-                // here we need to find a type of a variable
-                let var_name = m.captures[1].node.utf8_text(contents).unwrap();
-                log::debug!("var name is  {}", &var_name);
-                if let Some(var_type) = self.variables.get(var_name) {
-                    log::debug!("I found type name {}", var_type);
+                // the object can be a variable or a chain of calls
+                let object = &m.captures[1].node;
+                let types = if object.utf8_text(contents) == Ok("$this") {
+                    enclosing_class(uri, position).into_iter().collect()
+                } else {
+                    TypeInference::new(tree, contents).expression_types(object)
+                };
+                log::debug!("I found types {:?}", types);
+                if !types.is_empty() {
+                    // inherited and trait methods are offered too, for every arm of a union type
                     if let Some(results) = DB.with_borrow_mut(|db| {
                         if let Some(db) = db {
-                            let results = db
-                                .find_by_fqn_like(format!("{}::%", var_type).as_str())
-                                .unwrap();
-                            Some(results)
+                            Some(find_members_of(db, &types))
                         } else {
                             None
                         }
                     }) {
                         //process db result
-                        return results
+                        let methods = results
                             .iter()
-                            .map(|x| {
+                            .filter(|(x, _)| x.kind == ClassRecordKind::Method)
+                            .map(|(x, var_type)| {
                                 //use label detail to show return type
-                                let method_name = &x.fqn[var_type.len() + 2..];
+                                let method_name = x.name();
                                 let label_details = Some(CompletionItemLabelDetails {
                                     detail: x
                                         .return_type
@@ -405,7 +710,8 @@ impl ServerState {
                                         .map(|((params, ret), method)| {
                                             format!("{}{}: {}", method, ret, params)
                                         }), // function signature
-                                    description: None, // filepath
+                                    description: inherited_from(x, var_type)
+                                        .map(|owner| format!("from {}", owner)),
                                 });
                                 let detail = None; //Some(x.fqn.clone());
                                 let documentation =
@@ -422,38 +728,168 @@ impl ServerState {
                                                       //&x.parameters.clone().unwrap_or("()".into())
                                     ),
                                     label_details,
-                                    kind: Some(CompletionItemKind::METHOD),
+                                    kind: Some(x.kind.completion_item_kind()),
                                     detail,
                                     documentation,
                                     insert_text,
                                     ..Default::default()
                                 }
-                            })
-                            .collect();
+                            });
+                        // static properties are not reachable through `->`
+                        let properties = results
+                            .iter()
+                            .filter(|(x, _)| x.kind == ClassRecordKind::Property && !x.is_static())
+                            .map(|(x, var_type)| {
+                                let property_name = x.name().trim_start_matches('$');
+                                CompletionItem {
+                                    label: property_name.to_string(),
+                                    label_details: Some(CompletionItemLabelDetails {
+                                        detail: x.return_type.as_ref().map(|t| format!(": {}", t)),
+                                        description: inherited_from(x, var_type)
+                                            .map(|owner| format!("from {}", owner)),
+                                    }),
+                                    kind: Some(x.kind.completion_item_kind()),
+                                    documentation: Some(Documentation::MarkupContent(
+                                        MarkupContent {
+                                            kind: MarkupKind::Markdown,
+                                            value: x.description.clone(),
+                                        },
+                                    )),
+                                    insert_text: Some(property_name.to_string()),
+                                    ..Default::default()
+                                }
+                            });
+                        return methods.chain(properties).collect();
                     }
-                };
+                }
             }
         }
-        vec![]
+        self.get_name_completions(tree, contents, position)
     }
-    fn index_current_buffer(&mut self) {
-        let contents = self.current_buffer.as_bytes();
-        let tree = get_parsed_tree(self.current_buffer.as_bytes()).unwrap();
-        let root_node = tree.root_node();
+    /// Completes bare names with functions and class-like symbols.
+    fn get_name_completions(
+        &self,
+        tree: &Tree,
+        contents: &[u8],
+        position: &Position,
+    ) -> Vec<CompletionItem> {
+        // the cursor stays right after the last typed character
+        let mut point = position_to_point(contents, *position);
+        point.column = point.column.saturating_sub(1);
+        let node = match tree.root_node().descendant_for_point_range(point, point) {
+            Some(node) if node.kind() == "name" => node,
+            _ => return vec![],
+        };
+        let is_member = node.parent().map_or(false, |p| {
+            matches!(
+                p.kind(),
+                "member_access_expression"
+                    | "member_call_expression"
+                    | "scoped_call_expression"
+                    | "class_constant_access_expression"
+                    | "namespace_name"
+                    | "qualified_name"
+            )
+        });
+        if is_member {
+            return vec![];
+        }
+        let prefix = node.utf8_text(contents).unwrap_or_default();
+        let namespace = get_namespace(&node, contents);
+        let mut prefixes = vec![format!("\\{}", prefix)];
+        if !namespace.is_empty() {
+            prefixes.push(format!("{}\\{}", namespace, prefix));
+        }
+        let results: Vec<_> = DB.with_borrow_mut(|db| {
+            db.as_mut().map_or(vec![], |db| {
+                prefixes
+                    .iter()
+                    .filter_map(|prefix| db.find_top_level_by_prefix(prefix).ok())
+                    .flatten()
+                    .collect()
+            })
+        });
+        results
+            .iter()
+            .filter(|x| {
+                matches!(
+                    x.kind,
+                    ClassRecordKind::Function | ClassRecordKind::Constant
+                ) || x.kind.is_class_like()
+            })
+            .map(|x| {
+                let name = x.name();
+                let label_details = Some(CompletionItemLabelDetails {
+                    detail: x.parameters.clone().map(|params| {
+                        format!(
+                            "{}{}",
+                            params,
+                            x.return_type
+                                .as_ref()
+                                .map_or("".to_string(), |rt| format!(": {}", rt))
+                        )
+                    }),
+                    description: Some(x.fqn.clone()),
+                });
+                let documentation = Some(Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: x.description.clone(),
+                }));
+                let insert_text = if x.kind == ClassRecordKind::Function {
+                    format!("{}()", name)
+                } else {
+                    name.to_string()
+                };
+                CompletionItem {
+                    label: name.to_string(),
+                    label_details,
+                    kind: Some(x.kind.completion_item_kind()),
+                    documentation,
+                    insert_text: Some(insert_text),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+    fn supports_links(&self, method: &str) -> bool {
+        let text_document = self.params.capabilities.text_document.as_ref();
+        let link_support = match method {
+            "textDocument/declaration" => text_document
+                .and_then(|x| x.declaration.as_ref())
+                .and_then(|x| x.link_support),
+            "textDocument/implementation" => text_document
+                .and_then(|x| x.implementation.as_ref())
+                .and_then(|x| x.link_support),
+            _ => text_document
+                .and_then(|x| x.definition.as_ref())
+                .and_then(|x| x.link_support),
+        };
+        link_support.unwrap_or(false)
+    }
+    /// Runs the callback against the open buffer of the URI
+    /// or against the file on disk if the document is not open.
+    fn with_document<T>(&self, uri: &Url, f: impl FnOnce(&Tree, &[u8]) -> Option<T>) -> Option<T> {
+        if let Some(document) = self.documents.get(uri) {
+            return f(&document.tree, document.as_bytes());
+        }
+        log::debug!("parsing a path {:?}", uri.path());
+        let contents = read(uri.to_file_path().ok()?).ok()?;
+        let tree = get_parsed_tree(&contents[..])?;
+        f(&tree, &contents)
+    }
+    fn index_buffer(&mut self, uri: &Url) {
+        let document = if let Some(document) = self.documents.get(uri) {
+            document
+        } else {
+            return;
+        };
+        let contents = document.as_bytes();
+        let root_node = document.tree.root_node();
         let queries = vec![
         // Namespace detection
         vec!["(namespace_use_declaration (namespace_use_clause (qualified_name(namespace_name_as_prefix (namespace_name) @ns_name) (name) @class_name) (namespace_aliasing_clause (name) @alias)?))",],
-        vec![
-            "(assignment_expression left:( (variable_name (name) @var_name) right:(object_creation_expression (name) @object_name)))",
-               "(assignment_expression 
-                left: (variable_name (name) @var_name)  
-                right: (object_creation_expression 
-                            (qualified_name (namespace_name_as_prefix (namespace_name) @ns_name) (name) @object_name)
-                ))",
-        ],
     ];
         let mut ns_map = HashMap::new();
-        let mut vars_map = HashMap::new();
         for (idx, query) in queries.iter().enumerate() {
             let query = query.join(" \n");
             let query = Query::new(language_php(), &query).ok().unwrap();
@@ -473,69 +909,321 @@ impl ServerState {
                         };
                         ns_map.insert(key.to_string(), fqn);
                     }
-                    1 => {
-                        log::debug!("Defining variables {}", m.captures.len());
-                        let var_name = m.captures[0].node.utf8_text(&contents).unwrap();
-                        if m.captures.len() > 2 {
-                            let ns_name = m.captures[1].node.utf8_text(&contents).unwrap();
-                            let class_name = m.captures[2].node.utf8_text(&contents).unwrap();
-                            log::debug!(
-                                "detected obect creation with namespace: {} ; {}",
-                                ns_name,
-                                class_name
-                            );
-                            let fqn = if let Some(fqn) = ns_map.get(ns_name) {
-                                log::debug!(
-                                    "detected map of variable to FQN {} => {}",
-                                    var_name,
-                                    fqn
-                                );
-                                format!("{}\\{}", fqn, class_name)
-                            } else {
-                                format!("{}\\{}", ns_name, class_name)
-                            };
-                            vars_map.insert(var_name.to_string(), fqn.clone());
-                        } else {
-                            let class_name_node = m.captures[1].node;
-
-                            let class_name = m.captures[1].node.utf8_text(&contents).unwrap();
-                            if let Some(fqn) = ns_map.get(class_name) {
-                                log::debug!(
-                                    "detected map of variable to FQN {} => {}",
-                                    var_name,
-                                    fqn
-                                );
-                                vars_map.insert(var_name.to_string(), fqn.clone());
-                            }
-                        }
-                    }
                     _ => panic!("Somethig went wrong!!!"),
                 }
             }
         }
-        self.variables = vars_map;
         self.namespaces = ns_map;
     }
 }
 struct ServerState {
     params: InitializeParams,
-    current_buffer: String,
+    documents: Documents,
     namespaces: HashMap<String, String>,
-    variables: HashMap<String, String>,
 }
 
 fn get_parsed_tree(source: &[u8]) -> Option<Tree> {
-    let mut parser = Parser::new();
-    parser
-        .set_language(tree_sitter_php::language_php())
-        .expect("Error loading PHP parsing support");
-    parser.parse(&source, None)
+    document::parse(source)
+}
+
+/// Detects the FQN of a symbol under the cursor for the references request.
+/// Besides usages it also handles the name of a class-like declaration itself.
+fn detect_reference_name(position: Position, tree: &Tree, contents: &[u8]) -> Option<String> {
+    let point = position_to_point(contents, position);
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    // the cursor can be placed on any part of a qualified name
+    while let Some(parent) = node.parent() {
+        if matches!(
+            parent.kind(),
+            "qualified_name" | "namespace_name" | "namespace_name_as_prefix"
+        ) {
+            node = parent;
+        } else {
+            break;
+        }
+    }
+    let parent = node.parent()?;
+    let is_declaration = matches!(
+        parent.kind(),
+        "class_declaration"
+            | "interface_declaration"
+            | "trait_declaration"
+            | "enum_declaration"
+            | "function_definition"
+    ) && parent.child_by_field_name("name") == Some(node);
+    if is_declaration {
+        let name = node.utf8_text(contents).ok()?;
+        return Some(format!("{}\\{}", get_namespace(&parent, contents), name));
+    }
+    if parent.kind() == "method_declaration" && parent.child_by_field_name("name") == Some(node) {
+        let name = node.utf8_text(contents).ok()?;
+        return Some(format!("{}::{}", get_class_fqn(&parent, contents)?, name));
+    }
+    if is_class_reference(&node, contents) {
+        let name = node.utf8_text(contents).ok()?;
+        let resolver = NameResolver::new(tree, contents);
+        return Some(resolver.resolve_class(name, &node, contents));
+    }
+    search_member_call_expressions(tree, contents, &position)
+}
+
+/// Detects a class constant (`Foo::BAR`, `self::BAR`) or a global constant (`BAR`)
+/// under the cursor and returns FQN of the declared one.
+fn detect_constant_name(position: &Position, tree: &Tree, contents: &[u8]) -> Option<String> {
+    let point = position_to_point(contents, *position);
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    while let Some(parent) = node.parent() {
+        if matches!(
+            parent.kind(),
+            "qualified_name" | "namespace_name" | "namespace_name_as_prefix"
+        ) {
+            node = parent;
+        } else {
+            break;
+        }
+    }
+    if !matches!(node.kind(), "name" | "qualified_name") {
+        return None;
+    }
+    let parent = node.parent()?;
+    let name = node.utf8_text(contents).ok()?;
+    if parent.kind() == "class_constant_access_expression" {
+        let scope = parent.named_child(0)?;
+        if scope == node {
+            return None;
+        }
+        let class_name = scope.utf8_text(contents).ok()?;
+        let class_fqn = resolve_scope(class_name, &scope, tree, contents)?;
+        // the constant can be declared by an ancestor
+        let declared = DB.with_borrow_mut(|db| db.as_mut()?.find_member(&class_fqn, name).ok());
+        return Some(match declared.flatten() {
+            Some(member) => member.fqn,
+            None => format!("{}::{}", class_fqn, name),
+        });
+    }
+    // any other name is a constant used in an expression
+    let is_other_symbol = is_class_reference(&node, contents)
+        || parent.child_by_field_name("name") == Some(node)
+        || matches!(
+            parent.kind(),
+            "function_call_expression"
+                | "member_access_expression"
+                | "member_call_expression"
+                | "nullsafe_member_access_expression"
+                | "nullsafe_member_call_expression"
+                | "scoped_call_expression"
+                | "scoped_property_access_expression"
+                | "namespace_definition"
+                | "namespace_use_clause"
+                | "attribute"
+                | "goto_statement"
+                | "named_label_statement"
+        );
+    if is_other_symbol {
+        return None;
+    }
+    let candidates = NameResolver::new(tree, contents).resolve_constant(name, &node, contents);
+    // PHP falls back to the global constant if the namespaced one is not declared
+    DB.with_borrow_mut(|db| {
+        let db = db.as_mut()?;
+        candidates
+            .into_iter()
+            .find(|fqn| db.find_one_by_fqn(fqn).is_ok())
+    })
+}
+
+/// Completes constants and enum cases after `Foo::`, `self::` or `static::`.
+/// The text is scanned instead of the tree as `Foo::` alone does not parse.
+fn scoped_completions(
+    document: &Document,
+    uri: &Url,
+    position: &Position,
+) -> Option<Vec<CompletionItem>> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_';
+    let before = &document.text[..document.offset(*position)];
+    let line = before.rsplit('\n').next().unwrap_or_default();
+    let scope = line.trim_end_matches(is_name_char);
+    let prefix = &line[scope.len()..];
+    let scope = scope.strip_suffix("::")?;
+    let class_name = &scope[scope
+        .trim_end_matches(|c| is_name_char(c) || c == '\\')
+        .len()..];
+    // `$object::` is not a class name
+    if class_name.is_empty() || scope[..scope.len() - class_name.len()].ends_with('$') {
+        return None;
+    }
+    let point = position_to_point(document.as_bytes(), *position);
+    let node = document
+        .tree
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+    let contents = document.as_bytes();
+    let class_fqn = match class_name.to_lowercase().as_str() {
+        "self" | "static" => enclosing_class(uri, position)?,
+        _ => resolve_scope(class_name, &node, &document.tree, contents)?,
+    };
+    let results = DB.with_borrow_mut(|db| db.as_mut()?.find_members(&class_fqn).ok())?;
+    let items = results
+        .iter()
+        .filter(|x| {
+            matches!(
+                x.kind,
+                ClassRecordKind::Constant | ClassRecordKind::EnumCase
+            ) && x.name().starts_with(prefix)
+        })
+        .map(|x| CompletionItem {
+            label: x.name().to_string(),
+            label_details: Some(CompletionItemLabelDetails {
+                detail: x.value.as_ref().map(|v| format!(" = {}", v)),
+                description: None,
+            }),
+            kind: Some(x.kind.completion_item_kind()),
+            documentation: Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: x.description.clone(),
+            })),
+            insert_text: Some(x.name().to_string()),
+            ..Default::default()
+        })
+        .collect();
+    Some(items)
+}
+
+/// FQN of the class-like declaration around the position, looked up in the index
+/// which is kept up to date with the open buffer.
+fn enclosing_class(uri: &Url, position: &Position) -> Option<String> {
+    DB.with_borrow_mut(|db| {
+        let enclosing = db.as_mut()?.find_enclosing(uri, position).ok()?;
+        enclosing
+            .into_iter()
+            .find(|x| x.kind.is_class_like())
+            .map(|x| x.fqn)
+    })
+}
+
+/// Resolves the class part of `Foo::`, including `self`, `static` and `parent`, to FQN.
+fn resolve_scope(name: &str, node: &Node, tree: &Tree, contents: &[u8]) -> Option<String> {
+    match name.to_lowercase().as_str() {
+        "self" | "static" => get_class_fqn(node, contents),
+        "parent" => {
+            let class_fqn = get_class_fqn(node, contents)?;
+            DB.with_borrow_mut(|db| {
+                let supertypes = db.as_mut()?.find_supertypes(&class_fqn).ok()?;
+                supertypes
+                    .into_iter()
+                    .find(|x| x.kind == InheritanceKind::Extends)
+                    .map(|x| x.parent_fqn)
+            })
+        }
+        _ => Some(NameResolver::new(tree, contents).resolve_class(name, node, contents)),
+    }
+}
+
+/// Class or trait declaring the member when it is not the class the member is looked up on.
+fn inherited_from<'a>(member: &'a ClassRecord, class_fqn: &str) -> Option<&'a str> {
+    member
+        .container_name()
+        .filter(|&owner| owner != class_fqn.trim_start_matches('\\'))
+}
+
+/// Hover of a member looked up on the class, inherited members say where they come from.
+fn member_hover(member: &ClassRecord, class_fqn: &str) -> Hover {
+    let mut hover = record_hover(member);
+    if let (HoverContents::Array(contents), Some(owner)) =
+        (&mut hover.contents, inherited_from(member, class_fqn))
+    {
+        contents.push(MarkedString::String(format!("inherited from `{}`", owner)));
+    }
+    hover
 }
-fn detect_class_name(position: Position, path: String) -> Option<String> {
-    log::debug!("parsing a path {:?}", path);
-    let contents = read(&path).ok()?;
-    let tree = get_parsed_tree(&contents[..])?;
-    search_member_call_expressions(&tree, &contents, &position)
+
+/// Looks the member up on every type of a union and returns it with the type that has it.
+fn find_member_of(types: &[String], name: &str) -> Option<(ClassRecord, String)> {
+    DB.with_borrow_mut(|db| {
+        let db = db.as_mut()?;
+        types
+            .iter()
+            .filter(|class_fqn| !class_fqn.ends_with("[]"))
+            .find_map(|class_fqn| {
+                let member = db.find_member(class_fqn, name).ok()??;
+                Some((member, class_fqn.clone()))
+            })
+    })
+}
+
+/// Members of every type of a union, a name already offered by a previous type is skipped.
+/// Every member comes with the type it was found on.
+fn find_members_of(db: &mut Db, types: &[String]) -> Vec<(ClassRecord, String)> {
+    let mut names = HashSet::new();
+    let mut members = vec![];
+    for class_fqn in types.iter().filter(|class_fqn| !class_fqn.ends_with("[]")) {
+        for member in db.find_members(class_fqn).unwrap_or_default() {
+            if names.insert(member.name().to_string()) {
+                members.push((member, class_fqn.clone()));
+            }
+        }
+    }
+    members
+}
+
+/// Links let the client highlight the whole declaration and select its name.
+fn goto_response(list: Vec<ClassRecord>, link_support: bool) -> GotoDefinitionResponse {
+    if link_support {
+        GotoDefinitionResponse::Link(list.iter().map(|row| row.location_link()).collect())
+    } else {
+        GotoDefinitionResponse::Array(list.into_iter().map(|row| row.location).collect())
+    }
+}
+
+fn type_hierarchy_item(record: &ClassRecord) -> TypeHierarchyItem {
+    TypeHierarchyItem {
+        name: record.name().to_string(),
+        kind: record.kind.symbol_kind(),
+        tags: None,
+        detail: record.container_name().map(|x| x.to_string()),
+        uri: record.location.uri.clone(),
+        range: record.range,
+        selection_range: record.location.range,
+        data: Some(serde_json::Value::String(record.fqn.clone())),
+    }
+}
+
+/// Direct parents and interfaces of the class-like symbol or its direct subtypes.
+/// Traits are not types, so `use` edges are left out.
+fn related_types(fqn: &str, supertypes: bool) -> Vec<TypeHierarchyItem> {
+    DB.with_borrow_mut(|db| {
+        let db = match db {
+            Some(db) => db,
+            None => return vec![],
+        };
+        let edges = if supertypes {
+            db.find_supertypes(fqn)
+        } else {
+            db.find_subtypes(fqn)
+        };
+        edges
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|x| x.kind != InheritanceKind::UsesTrait)
+            .map(|x| if supertypes { x.parent_fqn } else { x.fqn })
+            .flat_map(|fqn| db.find_by_fqn(&fqn).unwrap_or_default())
+            .map(|x| type_hierarchy_item(&x))
+            .collect()
+    })
+}
+
+/// Hover with the PHP signature of the symbol and its description.
+fn record_hover(record: &ClassRecord) -> Hover {
+    Hover {
+        contents: HoverContents::Array(vec![
+            MarkedString::LanguageString(LanguageString {
+                value: record.signature(),
+                language: "php".to_string(),
+            }),
+            MarkedString::String(record.description.clone()),
+        ]),
+        range: None,
+    }
 }
 
 fn search_member_call_expressions(
@@ -555,7 +1243,7 @@ fn search_member_call_expressions(
                             (qualified_name (namespace_name_as_prefix (namespace_name) @ns_name) (name) @object_name)
                 ))",
         ],
-        vec!["(member_call_expression (variable_name (name) @varname) (name) @method_name)",
+        vec!["[(member_call_expression object: (_) @object name: (name) @method_name) (nullsafe_member_call_expression object: (_) @object name: (name) @method_name)]",
         "(member_call_expression (parenthesized_expression (object_creation_expression (name) @class_name) ) (name) @method_name)",
       "
 (member_call_expression 
@@ -571,6 +1259,8 @@ fn search_member_call_expressions(
 )",
             "(scoped_call_expression (name) @class_name (name) @method_name)"
         ],
+        // Function call expression
+        vec!["(function_call_expression function: [(name) (qualified_name)] @function_name)"],
         // Object creation expression
         vec!["(object_creation_expression (qualified_name (namespace_name_as_prefix (namespace_name) @ns_name) (name) @class_name))"]
     ];
@@ -640,12 +1330,13 @@ fn search_member_call_expressions(
                     log::debug!("node position = {:?}", m.captures[1].node.range());
                     let found = match m.pattern_index {
                         0 if m.captures[1].node.range().includes(&position) => {
-                            let var_name = m.captures[0].node.utf8_text(&contents).unwrap();
+                            let object = m.captures[0].node.utf8_text(&contents).unwrap();
                             let method_name = m.captures[1].node.utf8_text(&contents).unwrap();
-                            log::debug!("Expecting {}->{}()", &var_name, &method_name);
-                            vars_map
-                                .get(var_name)
-                                .map(|fqn| format!("{}::{}", fqn, method_name))
+                            log::debug!("Expecting {}->{}()", &object, &method_name);
+                            // the object can be a variable or a chain of calls
+                            let types = TypeInference::new(tree, contents)
+                                .expression_types(&m.captures[0].node);
+                            find_member_of(&types, method_name).map(|(x, _)| x.fqn)
                         }
 
                         1 | 3
@@ -693,6 +1384,21 @@ fn search_member_call_expressions(
                     }
                 }
                 3 => {
+                    let function_name_node = m.captures[0].node;
+                    if !function_name_node.range().includes(position) {
+                        continue;
+                    }
+                    let function_name = function_name_node.utf8_text(&contents).unwrap();
+                    let namespace = get_namespace(&function_name_node, contents);
+                    log::debug!("Expecting {}() in namespace {}", &function_name, &namespace);
+                    return Some(resolve_function_fqn(
+                        function_name,
+                        &function_name_node,
+                        tree,
+                        contents,
+                    ));
+                }
+                4 => {
                     log::debug!("searching for object creation references");
                     let ns_name = m.captures[0].node.utf8_text(&contents).unwrap();
                     let class_name = m.captures[1].node.utf8_text(&contents).unwrap();
@@ -707,3 +1413,19 @@ fn search_member_call_expressions(
     }
     None
 }
+/// Resolves a called function name to FQN, `use function` imports included.
+/// PHP falls back to the global function when the namespaced one does not exist,
+/// so the index decides which one to use.
+fn resolve_function_fqn(name: &str, node: &Node, tree: &Tree, contents: &[u8]) -> String {
+    let candidates = NameResolver::new(tree, contents).resolve_function(name, node, contents);
+    let declared = DB.with_borrow_mut(|db| {
+        let db = db.as_mut()?;
+        candidates
+            .iter()
+            .find(|fqn| db.find_one_by_fqn(fqn).is_ok())
+            .cloned()
+    });
+    declared
+        .or_else(|| candidates.last().cloned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use lsp_types::{CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall};
+use serde_json::Value;
+
+use crate::db::{ClassRecord, ClassRecordKind, Db, ReferenceRecord};
+
+/// Calls are attributed to functions and methods only, code outside of them has no caller.
+fn is_callable(kind: &ClassRecordKind) -> bool {
+    matches!(kind, ClassRecordKind::Method | ClassRecordKind::Function)
+}
+
+/// FQN of the declaration travels in `data` between the prepare and the calls requests.
+pub fn call_hierarchy_item(record: &ClassRecord) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: record.name().to_string(),
+        kind: record.kind.symbol_kind(),
+        tags: None,
+        detail: record.container_name().map(|x| x.to_string()),
+        uri: record.location.uri.clone(),
+        range: record.range,
+        selection_range: record.location.range,
+        data: Some(Value::String(record.fqn.clone())),
+    }
+}
+
+pub fn item_fqn(item: &CallHierarchyItem) -> Option<&str> {
+    item.data.as_ref().and_then(|data| data.as_str())
+}
+
+/// Items of the functions and methods declared with the FQN.
+pub fn prepare(db: &mut Db, fqn: &str) -> sqlx::Result<Vec<CallHierarchyItem>> {
+    Ok(db
+        .find_by_fqn(fqn)?
+        .iter()
+        .filter(|x| is_callable(&x.kind))
+        .map(call_hierarchy_item)
+        .collect())
+}
+
+/// Functions and methods calling the given one, each with the ranges of its calls.
+pub fn incoming_calls(db: &mut Db, fqn: &str) -> sqlx::Result<Vec<CallHierarchyIncomingCall>> {
+    let mut calls: Vec<CallHierarchyIncomingCall> = vec![];
+    let mut callers: HashMap<u32, usize> = HashMap::new();
+    for reference in db.find_hierarchy_references(fqn)? {
+        if !is_callable(&reference.kind) {
+            continue;
+        }
+        let caller = db
+            .find_enclosing(&reference.location.uri, &reference.location.range.start)?
+            .into_iter()
+            .find(|x| is_callable(&x.kind));
+        let caller = match caller {
+            Some(caller) => caller,
+            None => continue,
+        };
+        match callers.get(&caller.id) {
+            Some(&index) => calls[index].from_ranges.push(reference.location.range),
+            None => {
+                callers.insert(caller.id, calls.len());
+                calls.push(CallHierarchyIncomingCall {
+                    from: call_hierarchy_item(&caller),
+                    from_ranges: vec![reference.location.range],
+                });
+            }
+        }
+    }
+    Ok(calls)
+}
+
+/// Functions and methods called in the body of the item, each with the ranges of the calls.
+pub fn outgoing_calls(
+    db: &mut Db,
+    item: &CallHierarchyItem,
+) -> sqlx::Result<Vec<CallHierarchyOutgoingCall>> {
+    let mut calls: Vec<CallHierarchyOutgoingCall> = vec![];
+    let mut callees: HashMap<u32, usize> = HashMap::new();
+    for reference in db.find_references_in(&item.uri, &item.range)? {
+        let callee = match find_callee(db, &reference)? {
+            Some(callee) => callee,
+            None => continue,
+        };
+        match callees.get(&callee.id) {
+            Some(&index) => calls[index].from_ranges.push(reference.location.range),
+            None => {
+                callees.insert(callee.id, calls.len());
+                calls.push(CallHierarchyOutgoingCall {
+                    to: call_hierarchy_item(&callee),
+                    from_ranges: vec![reference.location.range],
+                });
+            }
+        }
+    }
+    Ok(calls)
+}
+
+/// Declaration the call points to, inherited methods included.
+fn find_callee(db: &mut Db, reference: &ReferenceRecord) -> sqlx::Result<Option<ClassRecord>> {
+    match reference.kind {
+        ClassRecordKind::Method => match reference.fqn.split_once("::") {
+            // the type of the object was not known while indexing
+            Some(("", _)) | None => Ok(None),
+            Some((class_fqn, method)) => db.find_member(class_fqn, method),
+        },
+        ClassRecordKind::Function => {
+            // the global function is called if the namespaced one is not declared
+            let candidates = std::iter::once(&reference.fqn).chain(reference.fallback_fqn.iter());
+            for fqn in candidates {
+                if let Some(callee) = db.find_by_fqn(fqn)?.into_iter().next() {
+                    return Ok(Some(callee));
+                }
+            }
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+/// Autoload rules of the project and its installed packages
+/// read from `composer.json` and `vendor/composer/installed.json`.
+#[derive(Debug, Default)]
+pub struct Autoload {
+    /// Namespace prefix (with the trailing `\`) and its directories.
+    psr4: Vec<(String, Vec<PathBuf>)>,
+    psr0: Vec<(String, Vec<PathBuf>)>,
+    classmap: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+impl Autoload {
+    /// Returns `None` if the project has no `composer.json`.
+    pub fn load<P: AsRef<Path>>(root_path: P) -> anyhow::Result<Option<Self>> {
+        let root_path = root_path.as_ref();
+        let composer_json = root_path.join("composer.json");
+        if !composer_json.is_file() {
+            return Ok(None);
+        }
+        let composer: Value = serde_json::from_slice(&fs::read(&composer_json)?)?;
+        let mut autoload = Self::default();
+        autoload.add_package(root_path, &composer["autoload"]);
+
+        let vendor_dir = root_path.join(
+            composer["config"]["vendor-dir"]
+                .as_str()
+                .unwrap_or("vendor"),
+        );
+        let installed_json = vendor_dir.join("composer").join("installed.json");
+        if installed_json.is_file() {
+            let installed: Value = serde_json::from_slice(&fs::read(&installed_json)?)?;
+            // Composer 2 wraps the list into `packages`, Composer 1 does not
+            let packages = installed["packages"]
+                .as_array()
+                .or(installed.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for package in packages.iter() {
+                let package_path = match package["install-path"].as_str() {
+                    Some(install_path) => vendor_dir.join("composer").join(install_path),
+                    None => match package["name"].as_str() {
+                        Some(name) => vendor_dir.join(name),
+                        None => continue,
+                    },
+                };
+                autoload.add_package(&package_path, &package["autoload"]);
+            }
+        }
+        autoload.sort_prefixes();
+        Ok(Some(autoload))
+    }
+    /// The most specific prefix wins.
+    fn sort_prefixes(&mut self) {
+        self.psr4.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        self.psr0.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+    }
+    fn add_package(&mut self, package_path: &Path, autoload: &Value) {
+        for (rules, key) in [(&mut self.psr4, "psr-4"), (&mut self.psr0, "psr-0")] {
+            if let Some(prefixes) = autoload[key].as_object() {
+                for (prefix, dirs) in prefixes {
+                    let dirs = paths(package_path, dirs);
+                    rules.push((prefix.trim_start_matches('\\').to_string(), dirs));
+                }
+            }
+        }
+        self.classmap
+            .extend(paths(package_path, &autoload["classmap"]));
+        self.files.extend(paths(package_path, &autoload["files"]));
+    }
+    /// Directories and files that contain autoloadable code.
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.psr4
+            .iter()
+            .chain(self.psr0.iter())
+            .flat_map(|(_, dirs)| dirs.iter())
+            .chain(self.classmap.iter())
+            .chain(self.files.iter())
+            .cloned()
+            .collect()
+    }
+    /// Returns the paths where the class-like symbol is expected to be declared.
+    pub fn resolve(&self, fqn: &str) -> Vec<PathBuf> {
+        let fqn = fqn.trim_start_matches('\\');
+        let mut candidates = vec![];
+        for (prefix, dirs) in self.psr4.iter() {
+            if let Some(relative) = fqn.strip_prefix(prefix.as_str()) {
+                let relative = format!("{}.php", relative.replace('\\', "/"));
+                candidates.extend(dirs.iter().map(|dir| dir.join(&relative)));
+            }
+        }
+        for (prefix, dirs) in self.psr0.iter() {
+            if fqn.starts_with(prefix.as_str()) {
+                // underscores in the class name are directory separators in PSR-0
+                let (namespace, class) = fqn.rsplit_once('\\').unwrap_or(("", fqn));
+                let mut relative = namespace.replace('\\', "/");
+                if !relative.is_empty() {
+                    relative.push('/');
+                }
+                relative.push_str(&class.replace('_', "/"));
+                relative.push_str(".php");
+                candidates.extend(dirs.iter().map(|dir| dir.join(&relative)));
+            }
+        }
+        candidates
+    }
+}
+
+/// Composer allows both a single path and a list of paths.
+fn paths(package_path: &Path, value: &Value) -> Vec<PathBuf> {
+    match value {
+        Value::String(path) => vec![package_path.join(path)],
+        Value::Array(paths) => paths
+            .iter()
+            .filter_map(|path| path.as_str())
+            .map(|path| package_path.join(path))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn autoload(rules: Value) -> Autoload {
+        let mut autoload = Autoload::default();
+        autoload.add_package(Path::new("/project"), &rules);
+        autoload.sort_prefixes();
+        autoload
+    }
+
+    #[test]
+    fn psr4_prefers_the_longest_prefix() {
+        let autoload = autoload(json!({
+            "psr-4": {"App\\": "src/", "App\\Tests\\": "tests/"}
+        }));
+        assert_eq!(
+            autoload.resolve("\\App\\Tests\\UserTest"),
+            [
+                PathBuf::from("/project/tests/UserTest.php"),
+                PathBuf::from("/project/src/Tests/UserTest.php"),
+            ]
+        );
+        assert_eq!(
+            autoload.resolve("App\\Models\\User"),
+            [PathBuf::from("/project/src/Models/User.php")]
+        );
+    }
+
+    #[test]
+    fn psr4_prefix_matches_whole_namespace_segments() {
+        let autoload = autoload(json!({"psr-4": {"\\App\\": "src/"}}));
+        assert!(autoload.resolve("Application\\Kernel").is_empty());
+        assert_eq!(
+            autoload.resolve("App\\Kernel"),
+            [PathBuf::from("/project/src/Kernel.php")]
+        );
+    }
+
+    #[test]
+    fn psr4_prefix_can_map_to_multiple_directories() {
+        let autoload = autoload(json!({"psr-4": {"App\\": ["src/", "lib/"], "": "fallback/"}}));
+        assert_eq!(
+            autoload.resolve("App\\Kernel"),
+            [
+                PathBuf::from("/project/src/Kernel.php"),
+                PathBuf::from("/project/lib/Kernel.php"),
+                PathBuf::from("/project/fallback/App/Kernel.php"),
+            ]
+        );
+    }
+
+    #[test]
+    fn psr0_maps_underscores_of_the_class_name() {
+        let autoload = autoload(json!({"psr-0": {"Twig_": "lib/"}}));
+        assert_eq!(
+            autoload.resolve("Twig_Extension_Core"),
+            [PathBuf::from("/project/lib/Twig/Extension/Core.php")]
+        );
+    }
+}
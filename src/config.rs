@@ -0,0 +1,179 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Project settings read from `.php-ls.toml` next to the project root.
+/// Keys sent in `initializationOptions` override the ones from the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Globs relative to the project root. Everything is included if the list is empty.
+    pub include: Vec<String>,
+    /// Globs relative to the project root, applied to both files and directories.
+    pub exclude: Vec<String>,
+    /// Extensions of files parsed as PHP.
+    pub extensions: Vec<String>,
+    /// Files bigger than this number of bytes are not indexed.
+    pub max_file_size: u64,
+    /// Directory of the index relative to the project, or `:memory:`.
+    /// The index goes to the user cache directory if it is not set.
+    pub database: Option<String>,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            include: vec![],
+            exclude: vec!["**/.git".to_string(), "**/node_modules".to_string()],
+            extensions: vec!["php".to_string()],
+            max_file_size: 2 * 1024 * 1024,
+            database: None,
+        }
+    }
+}
+impl Config {
+    const FILENAME: &'static str = ".php-ls.toml";
+
+    pub fn load<P: AsRef<Path>>(
+        root_path: P,
+        initialization_options: Option<&Value>,
+    ) -> anyhow::Result<Self> {
+        let filename = root_path.as_ref().join(Config::FILENAME);
+        let config = if filename.is_file() {
+            log::info!("Reading config {:?}", &filename);
+            toml::from_str(&fs::read_to_string(&filename)?)?
+        } else {
+            Config::default()
+        };
+        let overrides = match initialization_options {
+            Some(Value::Object(overrides)) => overrides,
+            _ => return Ok(config),
+        };
+        let mut value = serde_json::to_value(config)?;
+        if let Value::Object(config) = &mut value {
+            // the client may send options of other tools too
+            for (key, option) in overrides {
+                if config.contains_key(key) {
+                    config.insert(key.clone(), option.clone());
+                }
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+    /// Compiles globs of the config into a filter for files under the root.
+    pub fn file_filter<P: AsRef<Path>>(&self, root_path: P) -> anyhow::Result<FileFilter> {
+        Ok(FileFilter {
+            root_path: root_path.as_ref().to_path_buf(),
+            include: if self.include.is_empty() {
+                None
+            } else {
+                Some(glob_set(&self.include)?)
+            },
+            exclude: glob_set(&self.exclude)?,
+            extensions: self.extensions.clone(),
+            max_file_size: self.max_file_size,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+    root_path: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    extensions: Vec<String>,
+    max_file_size: u64,
+}
+impl FileFilter {
+    /// Checks whether the walker should skip the directory or file with everything inside.
+    pub fn is_excluded<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.exclude.is_match(self.relative(path.as_ref()))
+    }
+    /// Checks whether the file should be indexed.
+    pub fn is_indexed<P: AsRef<Path>>(&self, path: P, size: u64) -> bool {
+        let path = path.as_ref();
+        let relative = self.relative(path);
+        let has_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| self.extensions.iter().any(|x| x == ext));
+        has_extension
+            && size <= self.max_file_size
+            && !self.exclude.is_match(relative)
+            && self
+                .include
+                .as_ref()
+                .map_or(true, |include| include.is_match(relative))
+    }
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root_path).unwrap_or(path)
+    }
+}
+
+fn glob_set(globs: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> FileFilter {
+        let config = Config {
+            include: include.iter().map(|x| x.to_string()).collect(),
+            exclude: exclude.iter().map(|x| x.to_string()).collect(),
+            ..Config::default()
+        };
+        config.file_filter("/project").unwrap()
+    }
+
+    #[test]
+    fn default_excludes_vcs_and_node_modules() {
+        let filter = Config::default().file_filter("/project").unwrap();
+        assert!(filter.is_excluded("/project/.git"));
+        assert!(filter.is_excluded("/project/web/node_modules"));
+        assert!(!filter.is_excluded("/project/src"));
+        assert!(filter.is_indexed("/project/src/User.php", 100));
+    }
+
+    #[test]
+    fn include_globs_limit_indexed_files() {
+        let filter = filter(&["src/**", "app/*.php"], &[]);
+        assert!(filter.is_indexed("/project/src/Models/User.php", 100));
+        assert!(filter.is_indexed("/project/app/routes.php", 100));
+        assert!(!filter.is_indexed("/project/tests/UserTest.php", 100));
+    }
+
+    #[test]
+    fn exclude_globs_win_over_include_globs() {
+        let filter = filter(&["src/**"], &["vendor", "**/*Test.php"]);
+        assert!(filter.is_excluded("/project/vendor"));
+        assert!(!filter.is_excluded("/project/src/vendor"));
+        assert!(!filter.is_indexed("/project/src/UserTest.php", 100));
+        assert!(filter.is_indexed("/project/src/User.php", 100));
+    }
+
+    #[test]
+    fn extension_and_size_are_checked() {
+        let filter = filter(&[], &[]);
+        assert!(!filter.is_indexed("/project/src/User.js", 100));
+        assert!(!filter.is_indexed("/project/src/User.php", 3 * 1024 * 1024));
+    }
+
+    #[test]
+    fn initialization_options_override_known_keys() {
+        let options = json!({"exclude": ["vendor/**"], "unknown": true});
+        let config = Config::load("/nonexistent", Some(&options)).unwrap();
+        assert_eq!(config.exclude, ["vendor/**"]);
+        assert_eq!(config.extensions, ["php"]);
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::anyhow;
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     fs,
     path::{Path, PathBuf},
     rc::Rc,
@@ -8,34 +9,226 @@ use std::{
 };
 use tokio::runtime::Runtime;
 
-use lsp_types::{Location, Position, Range, Url};
+use lsp_types::{CompletionItemKind, Location, LocationLink, Position, Range, SymbolKind, Url};
 use serde::{Deserialize, Serialize};
 use sqlx::{
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteQueryResult, SqliteRow},
-    ConnectOptions, Executor, Row, SqliteConnection,
+    ConnectOptions, Connection, Executor, Row, SqliteConnection,
 };
 
-use crate::indexer::index::Index;
+use crate::indexer::{
+    index::{FileIndex, Index},
+    FileStamp,
+};
+use crate::search;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ClassRecord {
     pub id: u32,
     pub fqn: String,
     pub description: String,
+    /// Modifiers of a member as written: visibility, `static`, `readonly`...
     pub attributes: Option<String>,
     pub parameters: Option<String>,
+    /// Return type of a function or type of a property.
     pub return_type: Option<String>,
+    /// Classes of `return_type` (or of `@return`/`@var` in the docblock) as FQNs joined with `|`;
+    /// `self`, `static`, `$this` and `parent` are kept, scalar types are dropped.
+    pub type_fqn: Option<String>,
+    /// Default value of a property or value of a constant.
+    pub value: Option<String>,
+    /// Name of the symbol.
+    pub location: Location,
+    /// Whole declaration including its body.
+    pub range: Range,
+    pub kind: ClassRecordKind,
+}
+/// Usage of a symbol somewhere in the project.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReferenceRecord {
+    pub id: u32,
+    pub fqn: String,
+    /// Global function used when `fqn` turns out not to be declared anywhere.
+    pub fallback_fqn: Option<String>,
+    pub kind: ClassRecordKind,
+    pub location: Location,
+}
+/// Edge of the class hierarchy: `fqn` extends, implements or uses `parent_fqn`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InheritanceRecord {
+    pub id: u32,
+    pub fqn: String,
+    pub parent_fqn: String,
+    pub kind: InheritanceKind,
+    /// Name of the parent in the `extends`, `implements` or `use` clause.
     pub location: Location,
 }
+#[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum InheritanceKind {
+    Extends = 0,
+    Implements,
+    UsesTrait,
+}
 #[derive(Debug, Default, Serialize, Deserialize, sqlx::Type, Clone, PartialEq, PartialOrd)]
 #[repr(u8)]
 pub enum ClassRecordKind {
     #[default]
     Class = 0,
-    Intreface,
+    Interface,
     Base,
-    Atgtribute,
+    Attribute,
     Method,
+    Trait,
+    Enum,
+    EnumCase,
+    Function,
+    Property,
+    Constant,
+}
+impl ClassRecordKind {
+    pub fn completion_item_kind(&self) -> CompletionItemKind {
+        match self {
+            ClassRecordKind::Class | ClassRecordKind::Base | ClassRecordKind::Trait => {
+                CompletionItemKind::CLASS
+            }
+            ClassRecordKind::Interface => CompletionItemKind::INTERFACE,
+            ClassRecordKind::Attribute => CompletionItemKind::PROPERTY,
+            ClassRecordKind::Method => CompletionItemKind::METHOD,
+            ClassRecordKind::Enum => CompletionItemKind::ENUM,
+            ClassRecordKind::EnumCase => CompletionItemKind::ENUM_MEMBER,
+            ClassRecordKind::Function => CompletionItemKind::FUNCTION,
+            ClassRecordKind::Property => CompletionItemKind::PROPERTY,
+            ClassRecordKind::Constant => CompletionItemKind::CONSTANT,
+        }
+    }
+    pub fn symbol_kind(&self) -> SymbolKind {
+        match self {
+            ClassRecordKind::Class | ClassRecordKind::Base | ClassRecordKind::Trait => {
+                SymbolKind::CLASS
+            }
+            ClassRecordKind::Interface => SymbolKind::INTERFACE,
+            ClassRecordKind::Attribute => SymbolKind::PROPERTY,
+            ClassRecordKind::Method => SymbolKind::METHOD,
+            ClassRecordKind::Enum => SymbolKind::ENUM,
+            ClassRecordKind::EnumCase => SymbolKind::ENUM_MEMBER,
+            ClassRecordKind::Function => SymbolKind::FUNCTION,
+            ClassRecordKind::Property => SymbolKind::PROPERTY,
+            ClassRecordKind::Constant => SymbolKind::CONSTANT,
+        }
+    }
+    pub fn is_class_like(&self) -> bool {
+        matches!(
+            self,
+            ClassRecordKind::Class
+                | ClassRecordKind::Interface
+                | ClassRecordKind::Trait
+                | ClassRecordKind::Enum
+        )
+    }
+}
+impl ClassRecord {
+    /// Short name of the symbol: class name without namespace or member name without class.
+    pub fn name(&self) -> &str {
+        if let Some((_, name)) = self.fqn.rsplit_once("::") {
+            return name;
+        }
+        self.fqn
+            .rsplit_once("\\")
+            .map_or(self.fqn.as_str(), |(_, name)| name)
+    }
+    /// Class of a member or namespace of a top-level symbol, `None` in the global namespace.
+    pub fn container_name(&self) -> Option<&str> {
+        let container = match self.fqn.rsplit_once("::") {
+            Some((class, _)) => class,
+            None => self
+                .fqn
+                .rsplit_once('\\')
+                .map_or("", |(namespace, _)| namespace),
+        };
+        Some(container.trim_start_matches('\\')).filter(|x| !x.is_empty())
+    }
+    pub fn is_static(&self) -> bool {
+        self.attributes.as_deref().map_or(false, |modifiers| {
+            modifiers.split(' ').any(|x| x == "static")
+        })
+    }
+    /// Link to the declaration: the whole declaration is the target, the name is selected.
+    pub fn location_link(&self) -> LocationLink {
+        LocationLink {
+            origin_selection_range: None,
+            target_uri: self.location.uri.clone(),
+            target_range: self.range,
+            target_selection_range: self.location.range,
+        }
+    }
+    /// PHP signature used in hovers.
+    pub fn signature(&self) -> String {
+        let return_type = self
+            .return_type
+            .as_ref()
+            .map_or("".to_string(), |rt| format!(": {}", rt));
+        match self.kind {
+            ClassRecordKind::Method | ClassRecordKind::Function => format!(
+                "<?php function {}{}{} ?>\n\n---",
+                self.name(),
+                self.parameters.as_ref().unwrap_or(&"()".to_string()),
+                return_type
+            ),
+            ClassRecordKind::EnumCase => format!("<?php case {} ?>\n\n---", self.name()),
+            ClassRecordKind::Constant => {
+                let declaration: Vec<&str> = [
+                    self.attributes.as_deref(),
+                    Some("const"),
+                    self.return_type.as_deref(),
+                    Some(self.name()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                format!(
+                    "<?php {} = {}; ?>\n\n---",
+                    declaration.join(" "),
+                    self.value.as_deref().unwrap_or("?")
+                )
+            }
+            ClassRecordKind::Property => {
+                let declaration: Vec<&str> = [
+                    self.attributes.as_deref(),
+                    self.return_type.as_deref(),
+                    Some(self.name()),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                let default_value = self
+                    .value
+                    .as_ref()
+                    .map_or("".to_string(), |v| format!(" = {}", v));
+                format!(
+                    "<?php {}{}; ?>\n\n---",
+                    declaration.join(" "),
+                    default_value
+                )
+            }
+            _ => {
+                let keyword = match self.kind {
+                    ClassRecordKind::Interface => "interface",
+                    ClassRecordKind::Trait => "trait",
+                    ClassRecordKind::Enum => "enum",
+                    _ => "class",
+                };
+                let ns_name = self.fqn.rsplit_once("\\").map_or("", |(ns, _)| ns);
+                format!(
+                    "<?php\nnamespace {}\n{} {}{}\n---",
+                    ns_name,
+                    keyword,
+                    self.name(),
+                    return_type
+                )
+            }
+        }
+    }
 }
 
 impl sqlx::FromRow<'_, SqliteRow> for ClassRecord {
@@ -52,18 +245,134 @@ impl sqlx::FromRow<'_, SqliteRow> for ClassRecord {
         );
         let range = Range::new(pos_start, pos_end);
         let location: Location = Location::new(uri, range);
+        // rows without the declaration range fall back to the name
+        let full_range = match (
+            row.try_get("range_start_line")?,
+            row.try_get("range_start_character")?,
+            row.try_get("range_end_line")?,
+            row.try_get("range_end_character")?,
+        ) {
+            (Some(start_line), Some(start_character), Some(end_line), Some(end_character)) => {
+                Range::new(
+                    Position::new(start_line, start_character),
+                    Position::new(end_line, end_character),
+                )
+            }
+            _ => range,
+        };
 
         Ok(ClassRecord {
             id: row.try_get("id")?,
             fqn: row.try_get("fqn")?,
             description: row.try_get("description")?,
             location,
+            range: full_range,
             parameters: row.try_get("parameters")?,
             attributes: row.try_get("attributes")?,
             return_type: row.try_get("return_type")?,
+            type_fqn: row.try_get("type_fqn")?,
+            value: row.try_get("value")?,
+            kind: row.try_get("kind")?,
+        })
+    }
+}
+impl sqlx::FromRow<'_, SqliteRow> for ReferenceRecord {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let uri: &str = row.try_get::<'_, &str, &str>("location_uri")?;
+        let uri: Url = Url::from_str(uri).unwrap();
+        let pos_start = Position::new(
+            row.try_get("location_position_start_line")?,
+            row.try_get("location_position_start_character")?,
+        );
+        let pos_end = Position::new(
+            row.try_get("location_position_end_line")?,
+            row.try_get("location_position_end_character")?,
+        );
+        let range = Range::new(pos_start, pos_end);
+
+        Ok(ReferenceRecord {
+            id: row.try_get("id")?,
+            fqn: row.try_get("fqn")?,
+            fallback_fqn: row.try_get("fallback_fqn")?,
+            kind: row.try_get("kind")?,
+            location: Location::new(uri, range),
+        })
+    }
+}
+impl sqlx::FromRow<'_, SqliteRow> for InheritanceRecord {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let uri: &str = row.try_get::<'_, &str, &str>("location_uri")?;
+        let uri: Url = Url::from_str(uri).unwrap();
+        let pos_start = Position::new(
+            row.try_get("location_position_start_line")?,
+            row.try_get("location_position_start_character")?,
+        );
+        let pos_end = Position::new(
+            row.try_get("location_position_end_line")?,
+            row.try_get("location_position_end_character")?,
+        );
+
+        Ok(InheritanceRecord {
+            id: row.try_get("id")?,
+            fqn: row.try_get("fqn")?,
+            parent_fqn: row.try_get("parent_fqn")?,
+            kind: row.try_get("kind")?,
+            location: Location::new(uri, Range::new(pos_start, pos_end)),
+        })
+    }
+}
+/// Where the index is stored.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbLocation {
+    /// Throwaway index that lives as long as the process.
+    Memory,
+    /// Directory with the `php-ls.db` file.
+    Dir(PathBuf),
+}
+impl DbLocation {
+    const MEMORY: &'static str = ":memory:";
+
+    /// Resolves the `database` setting: `:memory:`, a directory (relative to the project)
+    /// or, if it is not set, a directory in the user cache unique for the project.
+    pub fn new<P: AsRef<Path>>(project_path: P, database: Option<&str>) -> anyhow::Result<Self> {
+        let project_path = project_path.as_ref();
+        Ok(match database {
+            Some(DbLocation::MEMORY) => DbLocation::Memory,
+            Some(dir) => DbLocation::Dir(project_path.join(dir)),
+            None => DbLocation::Dir(DbLocation::cache_dir(project_path)?),
         })
     }
+    /// `$XDG_CACHE_HOME/php-ls/<project>-<hash of the project path>`.
+    fn cache_dir(project_path: &Path) -> anyhow::Result<PathBuf> {
+        let cache_home = match std::env::var_os("XDG_CACHE_HOME").filter(|x| !x.is_empty()) {
+            Some(cache_home) => PathBuf::from(cache_home),
+            None => {
+                let home = std::env::var_os("HOME")
+                    .ok_or(anyhow!("Neither XDG_CACHE_HOME nor HOME is set"))?;
+                PathBuf::from(home).join(".cache")
+            }
+        };
+        let project_path = project_path
+            .canonicalize()
+            .unwrap_or_else(|_| project_path.to_path_buf());
+        let name = project_path
+            .file_name()
+            .map_or("root".into(), |name| name.to_string_lossy());
+        let dir = format!(
+            "{}-{:016x}",
+            name,
+            fnv1a(project_path.to_string_lossy().as_bytes())
+        );
+        Ok(cache_home.join("php-ls").join(dir))
+    }
+}
+/// FNV-1a hash, stable between runs and Rust versions unlike `DefaultHasher`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
 }
+
 #[derive(Debug)]
 pub struct Db {
     conn: SqliteConnection,
@@ -79,6 +388,8 @@ impl Index for Db {
 }
 impl Db {
     const FILENAME: &'static str = "php-ls.db";
+    /// Rows taken from each index before ranking the matches of a search.
+    const SEARCH_CANDIDATES: i64 = 2000;
     //const CREATE_DB: &'static str = r#"CREATE DATABASE"#;
     pub fn new<P: AsRef<Path>>(dirpath: P) -> Result<Self, anyhow::Error> {
         let filename = Db::get_db_filename(dirpath.as_ref())?;
@@ -98,35 +409,182 @@ impl Db {
         Ok(Self { rt, conn })
     }
 
+    /// Index kept in memory only, for tests and throwaway sessions.
+    pub fn in_memory() -> anyhow::Result<Self> {
+        let rt = Runtime::new()?;
+        let conn = rt.block_on(async {
+            SqliteConnectOptions::from_str("sqlite::memory:")?
+                .connect()
+                .await
+        })?;
+        Ok(Self { rt, conn })
+    }
+    pub fn open(location: &DbLocation) -> anyhow::Result<Self> {
+        match location {
+            DbLocation::Memory => Db::in_memory(),
+            DbLocation::Dir(dirpath) => Db::new(dirpath),
+        }
+    }
+
     fn get_db_filename<P: AsRef<Path>>(dirpath: P) -> anyhow::Result<PathBuf> {
         fs::create_dir_all(dirpath.as_ref())?;
         let mut filepath = dirpath.as_ref().to_path_buf();
         filepath.push(Db::FILENAME);
         Ok(filepath)
     }
-    pub fn setup(&mut self) -> sqlx::Result<SqliteQueryResult> {
-        let query = r#"
-CREATE TABLE IF NOT EXISTS fqn_declaration(
+    /// Ordered schema migrations. The number of applied ones is kept in `PRAGMA user_version`,
+    /// so a migration must never change once released: append a new one instead.
+    const MIGRATIONS: &'static [&'static str] = &[r#"
+CREATE TABLE fqn_declaration(
     id INTEGER NOT NULL PRIMARY KEY,
     fqn TEXT NOT NULL ,
+    name TEXT NOT NULL DEFAULT '',
     description TEXT NOT NULL,
     attributes TEXT,
     parameters TEXT,
     return_type TEXT,
+    type_fqn TEXT,
+    value TEXT,
     location_uri TEXT,
     location_position_start_line INTEGER,
     location_position_start_character INTEGER,
     location_position_end_line INTEGER,
-    location_position_end_character INTEGER
+    location_position_end_character INTEGER,
+    range_start_line INTEGER,
+    range_start_character INTEGER,
+    range_end_line INTEGER,
+    range_end_character INTEGER,
+    kind INTEGER NOT NULL DEFAULT 0
 );
-CREATE UNIQUE INDEX IF NOT EXISTS unique_fqn_declaration
+CREATE UNIQUE INDEX unique_fqn_declaration
 ON fqn_declaration(fqn,location_uri);
-        "#;
+CREATE INDEX fqn_declaration_location_uri ON fqn_declaration(location_uri);
+CREATE INDEX fqn_declaration_name ON fqn_declaration(name COLLATE NOCASE);
+CREATE VIRTUAL TABLE fqn_search USING fts5(
+    name,
+    content='fqn_declaration',
+    content_rowid='id',
+    tokenize='trigram'
+);
+CREATE TRIGGER fqn_search_insert AFTER INSERT ON fqn_declaration BEGIN
+    INSERT INTO fqn_search(rowid, name) VALUES (new.id, new.name);
+END;
+CREATE TRIGGER fqn_search_delete AFTER DELETE ON fqn_declaration BEGIN
+    INSERT INTO fqn_search(fqn_search, rowid, name) VALUES ('delete', old.id, old.name);
+END;
+CREATE TRIGGER fqn_search_update AFTER UPDATE OF name ON fqn_declaration BEGIN
+    INSERT INTO fqn_search(fqn_search, rowid, name) VALUES ('delete', old.id, old.name);
+    INSERT INTO fqn_search(rowid, name) VALUES (new.id, new.name);
+END;
+CREATE TABLE fqn_reference(
+    id INTEGER NOT NULL PRIMARY KEY,
+    fqn TEXT NOT NULL,
+    fallback_fqn TEXT,
+    kind INTEGER NOT NULL DEFAULT 0,
+    location_uri TEXT NOT NULL,
+    location_position_start_line INTEGER NOT NULL,
+    location_position_start_character INTEGER NOT NULL,
+    location_position_end_line INTEGER NOT NULL,
+    location_position_end_character INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX unique_fqn_reference
+ON fqn_reference(location_uri,location_position_start_line,location_position_start_character,fqn);
+CREATE INDEX fqn_reference_fqn ON fqn_reference(fqn);
+CREATE INDEX fqn_reference_fallback_fqn ON fqn_reference(fallback_fqn);
+CREATE TABLE fqn_inheritance(
+    id INTEGER NOT NULL PRIMARY KEY,
+    fqn TEXT NOT NULL,
+    parent_fqn TEXT NOT NULL,
+    kind INTEGER NOT NULL,
+    location_uri TEXT NOT NULL,
+    location_position_start_line INTEGER NOT NULL,
+    location_position_start_character INTEGER NOT NULL,
+    location_position_end_line INTEGER NOT NULL,
+    location_position_end_character INTEGER NOT NULL
+);
+CREATE INDEX fqn_inheritance_fqn ON fqn_inheritance(fqn);
+CREATE INDEX fqn_inheritance_parent_fqn ON fqn_inheritance(parent_fqn);
+CREATE INDEX fqn_inheritance_location_uri ON fqn_inheritance(location_uri);
+CREATE TABLE indexed_file(
+    uri TEXT NOT NULL PRIMARY KEY,
+    mtime INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    folder TEXT NOT NULL DEFAULT ''
+);
+"#];
 
-        self.rt.block_on(async { self.conn.execute(query).await })
+    /// Brings the schema up to date. An index that cannot be migrated is dropped
+    /// and created from scratch: it is only a cache of the project sources.
+    pub fn setup(&mut self) -> sqlx::Result<()> {
+        if let Err(e) = self.migrate() {
+            log::warn!("I cannot migrate the index, rebuilding it: {}", e);
+            self.drop_schema()?;
+            self.migrate()?;
+        }
+        Ok(())
+    }
+    fn migrate(&mut self) -> sqlx::Result<()> {
+        self.rt.block_on(async {
+            let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+                .fetch_one(&mut self.conn)
+                .await?;
+            let (tables,): (i64,) = sqlx::query_as(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            )
+            .fetch_one(&mut self.conn)
+            .await?;
+            let version = version as usize;
+            if version == 0 && tables > 0 {
+                return Err(sqlx::Error::Protocol(
+                    "the index was created without schema version".into(),
+                ));
+            }
+            if version > Db::MIGRATIONS.len() {
+                return Err(sqlx::Error::Protocol(format!(
+                    "the index has schema version {} newer than supported {}",
+                    version,
+                    Db::MIGRATIONS.len()
+                )));
+            }
+            for (applied, migration) in Db::MIGRATIONS.iter().enumerate().skip(version) {
+                log::info!("Applying index migration {}", applied + 1);
+                let mut tx = self.conn.begin().await?;
+                tx.execute(*migration).await?;
+                tx.execute(format!("PRAGMA user_version = {}", applied + 1).as_str())
+                    .await?;
+                tx.commit().await?;
+            }
+            Ok(())
+        })
+    }
+    fn drop_schema(&mut self) -> sqlx::Result<()> {
+        self.rt.block_on(async {
+            // virtual tables go first as dropping them drops their shadow tables too
+            let tables: Vec<(String,)> = sqlx::query_as(
+                r#"
+            SELECT name FROM sqlite_master
+            WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+            ORDER BY sql LIKE 'CREATE VIRTUAL%' DESC
+            "#,
+            )
+            .fetch_all(&mut self.conn)
+            .await?;
+            for (table,) in tables {
+                self.conn
+                    .execute(format!("DROP TABLE IF EXISTS \"{}\"", table).as_str())
+                    .await?;
+            }
+            self.conn.execute("PRAGMA user_version = 0").await?;
+            Ok(())
+        })
     }
     pub fn clean_index(&mut self) -> sqlx::Result<SqliteQueryResult> {
-        let query = r#"DELETE FROM fqn_declaration;"#;
+        let query = r#"
+DELETE FROM fqn_declaration;
+DELETE FROM fqn_reference;
+DELETE FROM fqn_inheritance;
+DELETE FROM indexed_file;
+        "#;
 
         self.rt.block_on(async { self.conn.execute(query).await })
     }
@@ -160,6 +618,26 @@ ON fqn_declaration(fqn,location_uri);
                 .await
         })
     }
+    /// Functions, constants and class-like symbols whose FQN starts with the prefix,
+    /// ignoring case. Class members are skipped.
+    pub fn find_top_level_by_prefix(&mut self, prefix: &str) -> sqlx::Result<Vec<ClassRecord>> {
+        // `_` and `%` typed by the user are literal; `\` separates namespaces, so `!` escapes
+        let pattern = format!(
+            "{}%",
+            prefix
+                .replace('!', "!!")
+                .replace('%', "!%")
+                .replace('_', "!_")
+        );
+        self.rt.block_on(async {
+            sqlx::query_as::<_, ClassRecord>(
+                r#"SELECT * FROM fqn_declaration WHERE fqn LIKE $1 ESCAPE '!' AND fqn NOT LIKE '%::%'"#,
+            )
+            .bind(pattern)
+            .fetch_all(&mut self.conn)
+            .await
+        })
+    }
     pub fn find_one_by_fqn_like(&mut self, name: &str) -> sqlx::Result<ClassRecord> {
         self.rt.block_on(async {
             sqlx::query_as::<_, ClassRecord>("SELECT * FROM fqn_declaration WHERE fqn like ?")
@@ -168,64 +646,745 @@ ON fqn_declaration(fqn,location_uri);
                 .await
         })
     }
+    /// Returns declarations whose body contains the position, the innermost first.
+    pub fn find_enclosing(
+        &mut self,
+        uri: &Url,
+        position: &Position,
+    ) -> sqlx::Result<Vec<ClassRecord>> {
+        self.rt.block_on(async {
+            sqlx::query_as::<_, ClassRecord>(
+                r#"
+            SELECT * FROM fqn_declaration WHERE location_uri = $1
+                AND (range_start_line < $2 OR (range_start_line = $2 AND range_start_character <= $3))
+                AND (range_end_line > $2 OR (range_end_line = $2 AND range_end_character >= $3))
+            ORDER BY range_start_line DESC, range_start_character DESC
+            "#,
+            )
+            .bind(uri.to_string())
+            .bind(position.line)
+            .bind(position.character)
+            .fetch_all(&mut self.conn)
+            .await
+        })
+    }
+    /// Returns the class-like declaration containing the method (or any other location).
     pub fn get_class_by_method_location(
         &mut self,
         location: &Location,
     ) -> sqlx::Result<ClassRecord> {
+        self.find_enclosing(&location.uri, &location.range.start)?
+            .into_iter()
+            .find(|x| x.kind.is_class_like())
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+    pub fn save_row(&mut self, symbol: &ClassRecord) -> sqlx::Result<SqliteQueryResult> {
+        self.rt
+            .block_on(async { insert_declaration(&mut self.conn, symbol).await })
+    }
+    pub fn save_reference(
+        &mut self,
+        reference: &ReferenceRecord,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        self.rt
+            .block_on(async { insert_reference(&mut self.conn, reference).await })
+    }
+    /// Replaces rows of every file in the batch within a single transaction.
+    pub fn save_file_indexes(&mut self, files: &[FileIndex]) -> sqlx::Result<()> {
+        self.rt.block_on(async {
+            let mut tx = self.conn.begin().await?;
+            for file in files {
+                delete_symbols(&mut tx, &file.uri).await?;
+                for symbol in file.declarations.iter() {
+                    insert_declaration(&mut tx, symbol).await?;
+                }
+                for reference in file.references.iter() {
+                    insert_reference(&mut tx, reference).await?;
+                }
+                for edge in file.inheritance.iter() {
+                    insert_inheritance(&mut tx, edge).await?;
+                }
+                match &file.stamp {
+                    Some(stamp) => {
+                        upsert_indexed_file(&mut tx, &file.uri, stamp, file.folder.as_deref())
+                            .await?;
+                    }
+                    // unsaved contents do not match any state of the file on disk
+                    None => {
+                        sqlx::query("DELETE FROM indexed_file WHERE uri = $1")
+                            .bind(&file.uri)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                }
+            }
+            tx.commit().await
+        })
+    }
+    /// Fuzzy search by short name for `workspace/symbol`, the best matches first.
+    /// Candidates come from the name index (exact names, then the first camel hump
+    /// as a prefix, shortest first) and the trigram index (substrings), then
+    /// `search::score` ranks them. Names with the query characters in order need
+    /// a scan, so they are only looked up when the other candidates do not fill the limit.
+    pub fn search_symbols(&mut self, query: &str, limit: usize) -> sqlx::Result<Vec<ClassRecord>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+        let first_hump = search::humps(query).first().copied().unwrap_or(query);
+        let prefix = format!("{}%", first_hump.replace(['%', '_'], ""));
+        let candidates = self.rt.block_on(async {
+            // a common prefix cannot push exact names out of the candidates
+            let mut candidates = sqlx::query_as::<_, ClassRecord>(
+                "SELECT * FROM fqn_declaration WHERE name = $1 COLLATE NOCASE LIMIT $2",
+            )
+            .bind(query)
+            .bind(Db::SEARCH_CANDIDATES)
+            .fetch_all(&mut self.conn)
+            .await?;
+            let prefixed = sqlx::query_as::<_, ClassRecord>(
+                "SELECT * FROM fqn_declaration WHERE name LIKE $1 ORDER BY length(name) LIMIT $2",
+            )
+            .bind(&prefix)
+            .bind(Db::SEARCH_CANDIDATES)
+            .fetch_all(&mut self.conn)
+            .await?;
+            candidates.extend(prefixed);
+            // the trigram tokenizer cannot match less than three characters
+            if query.chars().count() >= 3 {
+                let substrings = sqlx::query_as::<_, ClassRecord>(
+                    r#"
+                SELECT * FROM fqn_declaration WHERE id IN (
+                    SELECT rowid FROM fqn_search WHERE fqn_search MATCH $1 LIMIT $2
+                )
+                "#,
+                )
+                .bind(format!("\"{}\"", query.replace('"', "\"\"")))
+                .bind(Db::SEARCH_CANDIDATES)
+                .fetch_all(&mut self.conn)
+                .await?;
+                candidates.extend(substrings);
+            }
+            Ok::<_, sqlx::Error>(candidates)
+        })?;
+        let mut seen = HashSet::new();
+        let mut ranked: Vec<(u8, ClassRecord)> = candidates
+            .into_iter()
+            .filter(|x| seen.insert(x.id))
+            .filter_map(|x| search::score(query, x.name()).map(|score| (score, x)))
+            .collect();
+        if ranked.len() < limit {
+            let subsequences = self.find_subsequences(query)?;
+            ranked.extend(
+                subsequences
+                    .into_iter()
+                    .filter(|x| seen.insert(x.id))
+                    .filter_map(|x| search::score(query, x.name()).map(|score| (score, x))),
+            );
+        }
+        ranked.sort_by(|(a_score, a), (b_score, b)| {
+            a_score
+                .cmp(b_score)
+                .then(a.name().len().cmp(&b.name().len()))
+                .then(a.fqn.cmp(&b.fqn))
+        });
+        Ok(ranked.into_iter().take(limit).map(|(_, x)| x).collect())
+    }
+    /// Declarations whose short name contains the characters of the query in order.
+    fn find_subsequences(&mut self, query: &str) -> sqlx::Result<Vec<ClassRecord>> {
+        let mut pattern = String::from("%");
+        for c in query.chars() {
+            if matches!(c, '%' | '_' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+            pattern.push('%');
+        }
         self.rt.block_on(async {
             sqlx::query_as::<_, ClassRecord>(
                 r#"
-            SELECT * FROM symbol WHERE location_uri = ?
-                AND location_position_start_line <= ?
-                AND location_position_end_line  >= ?
+            SELECT * FROM fqn_declaration WHERE name LIKE $1 ESCAPE '\'
+            ORDER BY length(name) LIMIT $2
             "#,
             )
-            .bind(location.uri.to_string())
-            .bind(location.range.start.line)
-            .bind(location.range.end.line)
-            .fetch_one(&mut self.conn)
+            .bind(pattern)
+            .bind(Db::SEARCH_CANDIDATES)
+            .fetch_all(&mut self.conn)
             .await
         })
     }
-    pub fn save_row(&mut self, symbol: &ClassRecord) -> sqlx::Result<SqliteQueryResult> {
+    /// Usages of the symbol. A call of an unqualified function inside a namespace counts
+    /// for the global function as long as the namespaced one is not declared, the same way
+    /// PHP resolves it at runtime; the index keeps both candidates.
+    pub fn find_references(&mut self, fqn: &str) -> sqlx::Result<Vec<ReferenceRecord>> {
         self.rt.block_on(async {
-            sqlx::query(
+            sqlx::query_as::<_, ReferenceRecord>(
                 r#"
-            INSERT INTO fqn_declaration(
-                fqn, 
-                description,
-                attributes,
-                parameters,
-                return_type,
-                location_uri,
-                location_position_start_line,
-                location_position_start_character,
-                location_position_end_line,
-                location_position_end_character
+            SELECT * FROM fqn_reference r WHERE r.fqn = $1
+                AND (r.fallback_fqn IS NULL
+                    OR EXISTS (SELECT 1 FROM fqn_declaration d WHERE d.fqn = r.fqn))
+            UNION ALL
+            SELECT * FROM fqn_reference r WHERE r.fallback_fqn = $1 AND r.fqn != $1
+                AND NOT EXISTS (SELECT 1 FROM fqn_declaration d WHERE d.fqn = r.fqn)
+            "#,
             )
-
-            VALUES($1,$2,$3,$4,$5,$6,$7,$8,$9,$10) 
-            ON CONFLICT(fqn, location_uri) 
-            DO UPDATE SET
-                location_position_start_line = excluded.location_position_start_line,
-                location_position_start_character = excluded.location_position_start_character,
-                location_position_end_line = excluded.location_position_end_line,
-                location_position_end_character = excluded.location_position_end_character
-
+            .bind(fqn)
+            .fetch_all(&mut self.conn)
+            .await
+        })
+    }
+    /// Usages of the symbol including the ones made through the class hierarchy of a member:
+    /// `$child->method()` uses the parent's method if the child does not override it, and
+    /// `$parent->method()` typed by an interface or a parent can run an override.
+    pub fn find_hierarchy_references(&mut self, fqn: &str) -> sqlx::Result<Vec<ReferenceRecord>> {
+        let mut fqns = vec![fqn.to_string()];
+        if let Some((class_fqn, member)) = fqn.split_once("::") {
+            for descendant in self.find_descendants(class_fqn)? {
+                let inherited = format!("{}::{}", descendant, member);
+                if self.find_by_fqn(&inherited)?.is_empty() {
+                    fqns.push(inherited);
+                }
+            }
+            for ancestor in self.find_ancestors(class_fqn)?.into_iter().skip(1) {
+                let overridden = format!("{}::{}", ancestor, member);
+                if !fqns.contains(&overridden) && !self.find_by_fqn(&overridden)?.is_empty() {
+                    fqns.push(overridden);
+                }
+            }
+        }
+        let mut references = vec![];
+        for fqn in fqns {
+            references.extend(self.find_references(&fqn)?);
+        }
+        Ok(references)
+    }
+    /// Parents, interfaces and traits the class-like symbol declares directly.
+    pub fn find_supertypes(&mut self, fqn: &str) -> sqlx::Result<Vec<InheritanceRecord>> {
+        self.rt.block_on(async {
+            sqlx::query_as::<_, InheritanceRecord>(
+                "SELECT * FROM fqn_inheritance WHERE fqn = ? ORDER BY id",
+            )
+            .bind(fqn)
+            .fetch_all(&mut self.conn)
+            .await
+        })
+    }
+    /// Class-like symbols that extend, implement or use the given one directly.
+    pub fn find_subtypes(&mut self, fqn: &str) -> sqlx::Result<Vec<InheritanceRecord>> {
+        self.rt.block_on(async {
+            sqlx::query_as::<_, InheritanceRecord>(
+                "SELECT * FROM fqn_inheritance WHERE parent_fqn = ? ORDER BY id",
+            )
+            .bind(fqn)
+            .fetch_all(&mut self.conn)
+            .await
+        })
+    }
+    /// Class-like symbols inheriting from the given one directly or through other ones.
+    pub fn find_descendants(&mut self, fqn: &str) -> sqlx::Result<Vec<String>> {
+        let mut descendants = vec![];
+        let mut seen = HashSet::from([fqn.to_string()]);
+        let mut queue = VecDeque::from([fqn.to_string()]);
+        while let Some(fqn) = queue.pop_front() {
+            for edge in self.find_subtypes(&fqn)? {
+                if seen.insert(edge.fqn.clone()) {
+                    descendants.push(edge.fqn.clone());
+                    queue.push_back(edge.fqn);
+                }
+            }
+        }
+        Ok(descendants)
+    }
+    /// Declarations of the descendants of a class-like symbol, or the members
+    /// of the descendants overriding a member: implementations of an interface method.
+    pub fn find_implementations(&mut self, fqn: &str) -> sqlx::Result<Vec<ClassRecord>> {
+        let (class_fqn, member) = match fqn.split_once("::") {
+            Some((class_fqn, member)) => (class_fqn, Some(member)),
+            None => (fqn, None),
+        };
+        let mut implementations = vec![];
+        for descendant in self.find_descendants(class_fqn)? {
+            let fqn = match member {
+                Some(member) => format!("{}::{}", descendant, member),
+                None => descendant,
+            };
+            implementations.extend(self.find_by_fqn(&fqn)?);
+        }
+        Ok(implementations)
+    }
+    /// Returns the class-like symbol followed by its ancestors in the order PHP looks up members:
+    /// the class itself, its traits, the parent class with its ancestors, then interfaces.
+    pub fn find_ancestors(&mut self, fqn: &str) -> sqlx::Result<Vec<String>> {
+        let mut ancestors = vec![];
+        let mut seen = HashSet::new();
+        let mut stack = vec![fqn.to_string()];
+        while let Some(fqn) = stack.pop() {
+            // the hierarchy can be broken into a cycle while the code is edited
+            if !seen.insert(fqn.clone()) {
+                continue;
+            }
+            let mut supertypes = self.find_supertypes(&fqn)?;
+            let precedence = |kind: InheritanceKind| match kind {
+                InheritanceKind::UsesTrait => 0,
+                InheritanceKind::Extends => 1,
+                InheritanceKind::Implements => 2,
+            };
+            supertypes.sort_by_key(|x| precedence(x.kind));
+            stack.extend(supertypes.into_iter().rev().map(|x| x.parent_fqn));
+            ancestors.push(fqn);
+        }
+        Ok(ancestors)
+    }
+    /// Members of the class-like symbol including inherited ones;
+    /// a member hides the members of the same name declared by its ancestors.
+    pub fn find_members(&mut self, class_fqn: &str) -> sqlx::Result<Vec<ClassRecord>> {
+        let mut members = vec![];
+        let mut names = HashSet::new();
+        for ancestor in self.find_ancestors(class_fqn)? {
+            // a range on the FQN is case-sensitive and has no wildcards unlike LIKE,
+            // `:;` follows `::` and everything after it
+            let declared = self.rt.block_on(async {
+                sqlx::query_as::<_, ClassRecord>(
+                    "SELECT * FROM fqn_declaration WHERE fqn >= $1 AND fqn < $2 ORDER BY id",
+                )
+                .bind(format!("{}::", ancestor))
+                .bind(format!("{}:;", ancestor))
+                .fetch_all(&mut self.conn)
+                .await
+            })?;
+            for member in declared {
+                if names.insert(member.name().to_string()) {
+                    members.push(member);
+                }
+            }
+        }
+        Ok(members)
+    }
+    /// Finds the member by name in the class-like symbol or the nearest ancestor declaring it.
+    pub fn find_member(
+        &mut self,
+        class_fqn: &str,
+        name: &str,
+    ) -> sqlx::Result<Option<ClassRecord>> {
+        for ancestor in self.find_ancestors(class_fqn)? {
+            let member = self.rt.block_on(async {
+                sqlx::query_as::<_, ClassRecord>("SELECT * FROM fqn_declaration WHERE fqn = ?")
+                    .bind(format!("{}::{}", ancestor, name))
+                    .fetch_optional(&mut self.conn)
+                    .await
+            })?;
+            if member.is_some() {
+                return Ok(member);
+            }
+        }
+        Ok(None)
+    }
+    /// References located inside the range, in the order they appear in the file.
+    pub fn find_references_in(
+        &mut self,
+        uri: &Url,
+        range: &Range,
+    ) -> sqlx::Result<Vec<ReferenceRecord>> {
+        self.rt.block_on(async {
+            sqlx::query_as::<_, ReferenceRecord>(
+                r#"
+            SELECT * FROM fqn_reference WHERE location_uri = $1
+                AND (location_position_start_line > $2
+                    OR (location_position_start_line = $2 AND location_position_start_character >= $3))
+                AND (location_position_end_line < $4
+                    OR (location_position_end_line = $4 AND location_position_end_character <= $5))
+            ORDER BY location_position_start_line, location_position_start_character
             "#,
             )
-            .bind(symbol.fqn.clone())
-            .bind(symbol.description.clone())
-            .bind(symbol.attributes.clone())
-            .bind(symbol.parameters.clone())
-            .bind(symbol.return_type.clone())
-            .bind(symbol.location.uri.to_string())
-            .bind(symbol.location.range.start.line)
-            .bind(symbol.location.range.start.character)
-            .bind(symbol.location.range.end.line)
-            .bind(symbol.location.range.end.character)
+            .bind(uri.to_string())
+            .bind(range.start.line)
+            .bind(range.start.character)
+            .bind(range.end.line)
+            .bind(range.end.character)
+            .fetch_all(&mut self.conn)
+            .await
+        })
+    }
+    /// Returns stamps of files of the workspace folder keyed by URI.
+    pub fn get_indexed_files(&mut self, folder: &str) -> sqlx::Result<HashMap<String, FileStamp>> {
+        self.rt.block_on(async {
+            let rows: Vec<(String, i64, i64)> =
+                sqlx::query_as("SELECT uri, mtime, size FROM indexed_file WHERE folder = ?")
+                    .bind(folder)
+                    .fetch_all(&mut self.conn)
+                    .await?;
+            Ok(rows
+                .into_iter()
+                .map(|(uri, mtime, size)| (uri, FileStamp { mtime, size }))
+                .collect())
+        })
+    }
+    pub fn save_indexed_file(
+        &mut self,
+        uri: &str,
+        stamp: &FileStamp,
+        folder: Option<&str>,
+    ) -> sqlx::Result<SqliteQueryResult> {
+        self.rt
+            .block_on(async { upsert_indexed_file(&mut self.conn, uri, stamp, folder).await })
+    }
+    /// Removes all files of the workspace folder from the index.
+    pub fn delete_folder(&mut self, folder: &str) -> sqlx::Result<SqliteQueryResult> {
+        self.rt.block_on(async {
+            sqlx::query(
+                r#"
+            DELETE FROM fqn_declaration
+            WHERE location_uri IN (SELECT uri FROM indexed_file WHERE folder = $1);
+            DELETE FROM fqn_reference
+            WHERE location_uri IN (SELECT uri FROM indexed_file WHERE folder = $1);
+            DELETE FROM fqn_inheritance
+            WHERE location_uri IN (SELECT uri FROM indexed_file WHERE folder = $1);
+            DELETE FROM indexed_file WHERE folder = $1;
+            "#,
+            )
+            .bind(folder)
             .execute(&mut self.conn)
             .await
         })
     }
+    /// Removes everything the index knows about the file.
+    pub fn delete_file(&mut self, uri: &str) -> sqlx::Result<SqliteQueryResult> {
+        self.rt
+            .block_on(async { delete_file_rows(&mut self.conn, uri).await })
+    }
+}
+
+async fn insert_declaration(
+    conn: &mut SqliteConnection,
+    symbol: &ClassRecord,
+) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query(
+        r#"
+    INSERT INTO fqn_declaration(
+        fqn,
+        description,
+        attributes,
+        parameters,
+        return_type,
+        location_uri,
+        location_position_start_line,
+        location_position_start_character,
+        location_position_end_line,
+        location_position_end_character,
+        kind,
+        range_start_line,
+        range_start_character,
+        range_end_line,
+        range_end_character,
+        name,
+        value,
+        type_fqn
+    )
+
+    VALUES($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18)
+    ON CONFLICT(fqn, location_uri)
+    DO UPDATE SET
+        description = excluded.description,
+        attributes = excluded.attributes,
+        parameters = excluded.parameters,
+        return_type = excluded.return_type,
+        kind = excluded.kind,
+        range_start_line = excluded.range_start_line,
+        range_start_character = excluded.range_start_character,
+        range_end_line = excluded.range_end_line,
+        range_end_character = excluded.range_end_character,
+        location_position_start_line = excluded.location_position_start_line,
+        location_position_start_character = excluded.location_position_start_character,
+        location_position_end_line = excluded.location_position_end_line,
+        location_position_end_character = excluded.location_position_end_character,
+        name = excluded.name,
+        value = excluded.value,
+        type_fqn = excluded.type_fqn
+
+    "#,
+    )
+    .bind(symbol.fqn.clone())
+    .bind(symbol.description.clone())
+    .bind(symbol.attributes.clone())
+    .bind(symbol.parameters.clone())
+    .bind(symbol.return_type.clone())
+    .bind(symbol.location.uri.to_string())
+    .bind(symbol.location.range.start.line)
+    .bind(symbol.location.range.start.character)
+    .bind(symbol.location.range.end.line)
+    .bind(symbol.location.range.end.character)
+    .bind(symbol.kind.clone())
+    .bind(symbol.range.start.line)
+    .bind(symbol.range.start.character)
+    .bind(symbol.range.end.line)
+    .bind(symbol.range.end.character)
+    .bind(symbol.name())
+    .bind(symbol.value.clone())
+    .bind(symbol.type_fqn.clone())
+    .execute(conn)
+    .await
+}
+async fn insert_inheritance(
+    conn: &mut SqliteConnection,
+    edge: &InheritanceRecord,
+) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query(
+        r#"
+    INSERT INTO fqn_inheritance(
+        fqn,
+        parent_fqn,
+        kind,
+        location_uri,
+        location_position_start_line,
+        location_position_start_character,
+        location_position_end_line,
+        location_position_end_character
+    )
+    VALUES($1,$2,$3,$4,$5,$6,$7,$8)
+    "#,
+    )
+    .bind(edge.fqn.clone())
+    .bind(edge.parent_fqn.clone())
+    .bind(edge.kind)
+    .bind(edge.location.uri.to_string())
+    .bind(edge.location.range.start.line)
+    .bind(edge.location.range.start.character)
+    .bind(edge.location.range.end.line)
+    .bind(edge.location.range.end.character)
+    .execute(conn)
+    .await
+}
+async fn insert_reference(
+    conn: &mut SqliteConnection,
+    reference: &ReferenceRecord,
+) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query(
+        r#"
+    INSERT INTO fqn_reference(
+        fqn,
+        fallback_fqn,
+        kind,
+        location_uri,
+        location_position_start_line,
+        location_position_start_character,
+        location_position_end_line,
+        location_position_end_character
+    )
+    VALUES($1,$2,$3,$4,$5,$6,$7,$8)
+    ON CONFLICT DO NOTHING
+    "#,
+    )
+    .bind(reference.fqn.clone())
+    .bind(reference.fallback_fqn.clone())
+    .bind(reference.kind.clone())
+    .bind(reference.location.uri.to_string())
+    .bind(reference.location.range.start.line)
+    .bind(reference.location.range.start.character)
+    .bind(reference.location.range.end.line)
+    .bind(reference.location.range.end.character)
+    .execute(conn)
+    .await
+}
+async fn upsert_indexed_file(
+    conn: &mut SqliteConnection,
+    uri: &str,
+    stamp: &FileStamp,
+    folder: Option<&str>,
+) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query(
+        r#"
+    INSERT INTO indexed_file(uri, mtime, size, folder) VALUES($1,$2,$3,COALESCE($4,''))
+    ON CONFLICT(uri) DO UPDATE SET
+        mtime = excluded.mtime,
+        size = excluded.size,
+        folder = COALESCE($4, indexed_file.folder)
+    "#,
+    )
+    .bind(uri)
+    .bind(stamp.mtime)
+    .bind(stamp.size)
+    .bind(folder)
+    .execute(conn)
+    .await
+}
+async fn delete_symbols(conn: &mut SqliteConnection, uri: &str) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query(
+        r#"
+    DELETE FROM fqn_declaration WHERE location_uri = $1;
+    DELETE FROM fqn_reference WHERE location_uri = $1;
+    DELETE FROM fqn_inheritance WHERE location_uri = $1;
+    "#,
+    )
+    .bind(uri)
+    .execute(conn)
+    .await
+}
+async fn delete_file_rows(
+    conn: &mut SqliteConnection,
+    uri: &str,
+) -> sqlx::Result<SqliteQueryResult> {
+    sqlx::query(
+        r#"
+    DELETE FROM fqn_declaration WHERE location_uri = $1;
+    DELETE FROM fqn_reference WHERE location_uri = $1;
+    DELETE FROM fqn_inheritance WHERE location_uri = $1;
+    DELETE FROM indexed_file WHERE uri = $1;
+    "#,
+    )
+    .bind(uri)
+    .execute(conn)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::index_file;
+
+    fn db_with(files: &[(&str, &str)]) -> Db {
+        let mut db = Db::in_memory().unwrap();
+        db.setup().unwrap();
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, source)| {
+                let uri = Url::parse(&format!("file:///{}", path)).unwrap();
+                index_file(source.as_bytes(), &uri).unwrap()
+            })
+            .collect();
+        db.save_file_indexes(&files).unwrap();
+        db
+    }
+    fn fqns(records: &[ClassRecord]) -> Vec<&str> {
+        records.iter().map(|x| x.fqn.as_str()).collect()
+    }
+    fn user_version(db: &mut Db) -> i64 {
+        db.rt
+            .block_on(async {
+                sqlx::query_as::<_, (i64,)>("PRAGMA user_version")
+                    .fetch_one(&mut db.conn)
+                    .await
+            })
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn members_are_matched_by_exact_class_prefix() {
+        let mut db = db_with(&[(
+            "a.php",
+            r#"<?php
+class A_B { const C = 1; public $p; function m() {} }
+class AxB { function x() {} }
+class A_Bc { function w() {} }
+class a_b2 { function y() {} }
+"#,
+        )]);
+        let mut members = fqns(&db.find_members("\\A_B").unwrap())
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        members.sort();
+        assert_eq!(members, ["\\A_B::$p", "\\A_B::C", "\\A_B::m"]);
+        assert!(db.find_members("\\a_b").unwrap().is_empty());
+    }
+
+    #[test]
+    fn members_hide_the_ones_of_ancestors() {
+        let mut db = db_with(&[(
+            "a.php",
+            r#"<?php
+class P { function a() {} function b() {} }
+class C extends P { function b() {} }
+"#,
+        )]);
+        assert_eq!(fqns(&db.find_members("\\C").unwrap()), ["\\C::b", "\\P::a"]);
+        let member = db.find_member("\\C", "a").unwrap().unwrap();
+        assert_eq!(member.fqn, "\\P::a");
+    }
+
+    #[test]
+    fn top_level_prefix_is_literal_and_skips_members() {
+        let mut db = db_with(&[(
+            "a.php",
+            r#"<?php
+function a_b() {}
+function axb() {}
+class A_Bc { const A_B = 1; function a_b() {} }
+"#,
+        )]);
+        let mut found = fqns(&db.find_top_level_by_prefix("\\a_").unwrap())
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        found.sort();
+        assert_eq!(found, ["\\A_Bc", "\\a_b"]);
+        assert!(db.find_top_level_by_prefix("\\a%").unwrap().is_empty());
+    }
+
+    #[test]
+    fn hierarchy_is_walked_both_ways() {
+        let mut db = db_with(&[(
+            "a.php",
+            r#"<?php
+namespace App;
+interface I {}
+trait T {}
+class P implements I {}
+class C extends P { use T; }
+"#,
+        )]);
+        assert_eq!(
+            db.find_ancestors("App\\C").unwrap(),
+            ["App\\C", "App\\T", "App\\P", "App\\I"]
+        );
+        assert_eq!(db.find_descendants("App\\I").unwrap(), ["App\\P", "App\\C"]);
+        assert!(db.find_descendants("App\\C").unwrap().is_empty());
+    }
+
+    #[test]
+    fn unqualified_calls_fall_back_to_global_functions() {
+        let mut db = db_with(&[
+            ("a.php", "<?php\nnamespace App;\nstrlen('x');\nhelper();\n"),
+            ("b.php", "<?php\nnamespace App;\nfunction helper() {}\n"),
+        ]);
+        assert_eq!(db.find_references("\\strlen").unwrap().len(), 1);
+        assert!(db.find_references("App\\strlen").unwrap().is_empty());
+        assert_eq!(db.find_references("App\\helper").unwrap().len(), 1);
+        assert!(db.find_references("\\helper").unwrap().is_empty());
+    }
+
+    #[test]
+    fn references_include_calls_through_descendants() {
+        let mut db = db_with(&[(
+            "a.php",
+            r#"<?php
+class P { function m() {} }
+class C extends P {}
+function f(C $c) { $c->m(); }
+"#,
+        )]);
+        assert!(db.find_references("\\P::m").unwrap().is_empty());
+        assert_eq!(db.find_hierarchy_references("\\P::m").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn migrations_set_the_schema_version() {
+        let mut db = Db::in_memory().unwrap();
+        db.setup().unwrap();
+        assert_eq!(user_version(&mut db), Db::MIGRATIONS.len() as i64);
+        // applied migrations are not run again
+        db.setup().unwrap();
+        assert_eq!(user_version(&mut db), Db::MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn unversioned_index_is_rebuilt() {
+        let mut db = Db::in_memory().unwrap();
+        db.rt
+            .block_on(db.conn.execute("CREATE TABLE fqn_declaration(fqn TEXT)"))
+            .unwrap();
+        db.setup().unwrap();
+        assert_eq!(user_version(&mut db), Db::MIGRATIONS.len() as i64);
+        assert!(db.find_by_fqn("\\A").unwrap().is_empty());
+    }
 }
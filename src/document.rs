@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use lsp_types::{Position, TextDocumentContentChangeEvent, Url};
+use tree_sitter::{InputEdit, Parser, Point, Tree};
+
+/// Document opened in the editor: its latest text and the tree parsed from it.
+#[derive(Debug)]
+pub struct Document {
+    pub text: String,
+    pub version: i32,
+    pub tree: Tree,
+}
+impl Document {
+    pub fn new(text: String, version: i32) -> Option<Self> {
+        let tree = parse(text.as_bytes())?;
+        Some(Self {
+            text,
+            version,
+            tree,
+        })
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        self.text.as_bytes()
+    }
+    /// Applies changes in the order the client sent them and reparses the text
+    /// reusing the unchanged parts of the previous tree.
+    pub fn apply_changes(
+        &mut self,
+        changes: &[TextDocumentContentChangeEvent],
+        version: i32,
+    ) -> Option<()> {
+        let mut full_reparse = false;
+        for change in changes {
+            match change.range {
+                Some(range) => self.edit(range.start, range.end, &change.text),
+                None => {
+                    self.text = change.text.clone();
+                    full_reparse = true;
+                }
+            }
+        }
+        let old_tree = if full_reparse { None } else { Some(&self.tree) };
+        self.tree = reparse(self.text.as_bytes(), old_tree)?;
+        self.version = version;
+        Some(())
+    }
+    fn edit(&mut self, start: Position, end: Position, text: &str) {
+        let start_byte = self.offset(start);
+        let old_end_byte = self.offset(end).max(start_byte);
+        let start_position = self.point(start_byte);
+        let old_end_position = self.point(old_end_byte);
+        self.text.replace_range(start_byte..old_end_byte, text);
+        let new_end_byte = start_byte + text.len();
+        self.tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte,
+            start_position,
+            old_end_position,
+            new_end_position: self.point(new_end_byte),
+        });
+    }
+    /// Converts an LSP position, where the character counts UTF-16 code units,
+    /// to a byte offset in the text. Positions past the line end are clamped to it.
+    pub fn offset(&self, position: Position) -> usize {
+        offset(&self.text, position)
+    }
+    fn point(&self, offset: usize) -> Point {
+        point(&self.text, offset)
+    }
+}
+
+/// Converts an LSP position to the point of the same character in the tree of the contents.
+pub fn position_to_point(contents: &[u8], position: Position) -> Point {
+    let text = String::from_utf8_lossy(contents);
+    point(&text, offset(&text, position))
+}
+
+fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+    let mut units = 0;
+    for (index, c) in text[line_start..].char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Row and byte column of the offset as tree-sitter expects them.
+fn point(text: &str, offset: usize) -> Point {
+    let before = &text[..offset];
+    let row = before.matches('\n').count();
+    let column = before
+        .rfind('\n')
+        .map_or(offset, |index| offset - index - 1);
+    Point::new(row, column)
+}
+
+/// Open documents keyed by URI.
+#[derive(Debug, Default)]
+pub struct Documents {
+    documents: HashMap<Url, Document>,
+}
+impl Documents {
+    /// Stores the document, replacing the previous text of the same URI.
+    pub fn open(&mut self, uri: Url, text: String, version: i32) {
+        match Document::new(text, version) {
+            Some(document) => {
+                self.documents.insert(uri, document);
+            }
+            None => log::error!("I cannot parse {}", uri),
+        }
+    }
+    pub fn change(&mut self, uri: Url, changes: &[TextDocumentContentChangeEvent], version: i32) {
+        match self.documents.get_mut(&uri) {
+            Some(document) => {
+                if document.apply_changes(changes, version).is_none() {
+                    log::error!("I cannot parse {}", uri);
+                }
+            }
+            // a change of an unknown document can only be applied if it carries the whole text
+            None => match changes.last().filter(|change| change.range.is_none()) {
+                Some(change) => self.open(uri, change.text.clone(), version),
+                None => log::warn!("Received changes of not opened document {}", uri),
+            },
+        }
+    }
+    pub fn close(&mut self, uri: &Url) {
+        self.documents.remove(uri);
+    }
+    pub fn get(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+}
+
+pub fn parse(source: &[u8]) -> Option<Tree> {
+    reparse(source, None)
+}
+
+fn reparse(source: &[u8], old_tree: Option<&Tree>) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(tree_sitter_php::language_php())
+        .expect("Error loading PHP parsing support");
+    parser.parse(source, old_tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Range;
+
+    use super::*;
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn offset_counts_utf16_units() {
+        let document = Document::new("<?php\n$é = '😀x';\n".to_string(), 1).unwrap();
+        // `é` is one unit and two bytes
+        assert_eq!(document.offset(Position::new(1, 2)), 9);
+        // `😀` is two units and four bytes
+        assert_eq!(document.offset(Position::new(1, 8)), 17);
+        assert_eq!(&document.text[17..18], "x");
+    }
+
+    #[test]
+    fn offset_is_clamped_to_line_end() {
+        let document = Document::new("<?php\necho 1;\n".to_string(), 1).unwrap();
+        assert_eq!(document.offset(Position::new(0, 100)), 5);
+        assert_eq!(document.offset(Position::new(1, 100)), 13);
+        assert_eq!(document.offset(Position::new(9, 0)), document.text.len());
+    }
+
+    #[test]
+    fn position_is_converted_to_byte_column() {
+        let contents = "<?php\n$é = '😀x';\n".as_bytes();
+        assert_eq!(
+            position_to_point(contents, Position::new(1, 8)),
+            Point::new(1, 11)
+        );
+        assert_eq!(
+            position_to_point(contents, Position::new(1, 100)),
+            Point::new(1, 14)
+        );
+    }
+
+    #[test]
+    fn changes_are_applied_in_order() {
+        let mut document = Document::new("<?php\n$a = 1;\n$b = 2;\n".to_string(), 1).unwrap();
+        let changes = [
+            // the second edit sees the text produced by the first one
+            change((1, 0), (1, 2), "$😀"),
+            change((1, 3), (1, 3), "x"),
+            change((2, 5), (2, 6), "42"),
+        ];
+        document.apply_changes(&changes, 2).unwrap();
+        assert_eq!(document.text, "<?php\n$😀x = 1;\n$b = 42;\n");
+        assert_eq!(document.version, 2);
+        assert_eq!(document.tree.root_node().end_byte(), document.text.len());
+    }
+
+    #[test]
+    fn full_change_replaces_text() {
+        let mut document = Document::new("<?php\n$a = 1;\n".to_string(), 1).unwrap();
+        let changes = [
+            change((1, 0), (1, 2), "$b"),
+            TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "<?php\n".to_string(),
+            },
+            change((0, 5), (0, 5), " echo 1;"),
+        ];
+        document.apply_changes(&changes, 3).unwrap();
+        assert_eq!(document.text, "<?php echo 1;\n");
+    }
+}
@@ -1,109 +1,755 @@
-use std::{cell::RefCell, fmt::Debug, fs, path::Path};
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Ok};
 use lsp_types::{InitializeParams, Url};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use tree_sitter::Parser;
+use tree_sitter::{Node, Parser, Tree};
 
-use crate::{db::Db, indexer::index::Indexer, ParamsGetProjectPath, DB};
+use crate::{
+    composer::Autoload,
+    config::{Config, FileFilter},
+    db::{ClassRecord, ClassRecordKind, Db, DbLocation},
+    indexer::{
+        index::{FileIndex, Indexer},
+        name_resolver::NameResolver,
+    },
+    inference::{class_types, doc_tag_type},
+    ParamsGetProjectPath, ToLocation, DB, FOLDERS,
+};
 thread_local! {
 
-    pub static INDEXERS: RefCell<Vec<Box<dyn Indexer>>> = RefCell::new(vec![
+    /// Indexers of the symbols the document declares.
+    pub static DECLARATION_INDEXERS: RefCell<Vec<Box<dyn Indexer>>> = RefCell::new(vec![
        Box::new(class_declaration::ClassDeclarationIndexer::default()),
-       //Box::new(function_declarion::FunctionDeclarationIndexer::default()),
-        //Box::new(class_reference::ClassReferenceIndexer::default()),
+       Box::new(interface_declaration::InterfaceDeclarationIndexer::default()),
+       Box::new(trait_declaration::TraitDeclarationIndexer::default()),
+       Box::new(enum_declaration::EnumDeclarationIndexer::default()),
+       Box::new(function_declaration::FunctionDeclarationIndexer::default()),
+       Box::new(constant_declaration::ConstantDeclarationIndexer::default()),
+    ]);
+    /// Indexers of references and class hierarchy, run after the declaration ones.
+    pub static INDEXERS: RefCell<Vec<Box<dyn Indexer>>> = RefCell::new(vec![
+       Box::new(class_reference::ClassLikeReferenceIndexer::default()),
+       Box::new(function_reference::FunctionReferenceIndexer::default()),
+       Box::new(method_reference::MethodReferenceIndexer::default()),
+       Box::new(inheritance::InheritanceIndexer::default()),
     ]);
 }
 pub mod class_declaration;
-//pub mod class_reference;
-//pub mod function_declarion;
+pub mod class_reference;
+pub mod constant_declaration;
+pub mod enum_declaration;
+pub mod function_declaration;
+pub mod function_reference;
 pub mod index;
+pub mod inheritance;
+pub mod interface_declaration;
+pub mod method_reference;
+pub mod name_resolver;
+pub mod trait_declaration;
+
+/// Number of files written to the database in one transaction.
+const WRITE_BATCH_SIZE: usize = 256;
+
+/// Returns the text of a docblock placed right before the declaration node
+/// without the comment markers and `@tags`.
+pub fn get_description(node: &Node, document: &[u8]) -> String {
+    let comment = node
+        .prev_sibling()
+        .filter(|c| c.kind() == "comment")
+        .map(|c| c.utf8_text(document).ok())
+        .flatten()
+        .unwrap_or_default();
+    let desc: Vec<&str> = comment
+        .split("\n")
+        .map(|x| x.trim())
+        .filter(|&x| !x.starts_with("* @") && !x.starts_with("/**") && !x.starts_with("*/"))
+        .map(|x| if x.len() > 1 { x[1..].trim() } else { "" })
+        .collect();
+    desc.join("\n")
+}
+
+/// Detects the namespace the node belongs to.
+/// Handles both `namespace Foo;` and `namespace Foo { ... }` forms.
+pub fn get_namespace(node: &Node, document: &[u8]) -> String {
+    let mut current = Some(*node);
+    while let Some(node) = current {
+        if node.kind() == "namespace_definition" && node.child_by_field_name("body").is_some() {
+            return namespace_name(&node, document);
+        }
+        let mut sibling = node.prev_sibling();
+        while let Some(prev) = sibling {
+            if prev.kind() == "namespace_definition" && prev.child_by_field_name("body").is_none() {
+                return namespace_name(&prev, document);
+            }
+            sibling = prev.prev_sibling();
+        }
+        current = node.parent();
+    }
+    String::new()
+}
+fn namespace_name(node: &Node, document: &[u8]) -> String {
+    node.child_by_field_name("name")
+        .map(|n| n.utf8_text(document).ok())
+        .flatten()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Returns FQN of the class-like declaration enclosing the node.
+pub fn get_class_fqn(node: &Node, document: &[u8]) -> Option<String> {
+    let mut current = node.parent();
+    while let Some(node) = current {
+        if matches!(
+            node.kind(),
+            "class_declaration"
+                | "interface_declaration"
+                | "trait_declaration"
+                | "enum_declaration"
+        ) {
+            let name = node.child_by_field_name("name")?.utf8_text(document).ok()?;
+            return Some(format!("{}\\{}", get_namespace(&node, document), name));
+        }
+        current = node.parent();
+    }
+    None
+}
+
+/// Saves a class-like declaration (class, interface, trait, enum) and returns its FQN.
+pub fn save_class_like(
+    index: &mut FileIndex,
+    document: &[u8],
+    node: &Node,
+    kind: ClassRecordKind,
+    url: &Url,
+) -> anyhow::Result<String> {
+    let name_node = node
+        .child_by_field_name("name")
+        .ok_or(anyhow!("Declaration without name"))?;
+    let name = name_node.utf8_text(document)?;
+    let fqn = format!("{}\\{}", get_namespace(node, document), name);
+    log::debug!("{:?}'s FQN = {}", kind, fqn);
+    // backed enums keep their scalar type next to the name
+    let backed_type = if kind == ClassRecordKind::Enum {
+        let mut cursor = node.walk();
+        let backed_type = node
+            .named_children(&mut cursor)
+            .find(|n| n.kind().ends_with("_type"))
+            .map(|n| n.utf8_text(document).ok().map(|x| x.to_string()))
+            .flatten();
+        backed_type
+    } else {
+        None
+    };
+    let record = ClassRecord {
+        id: 0,
+        fqn: fqn.clone(),
+        description: get_description(node, document),
+        location: name_node.range().to_locaton(url),
+        range: node.range().to_locaton(url).range,
+        parameters: None,
+        attributes: None,
+        return_type: backed_type,
+        type_fqn: None,
+        value: None,
+        kind,
+    };
+    index.save_row(record);
+    Ok(fqn)
+}
+
+/// Saves methods, properties, constants and enum cases declared in the body of a class-like declaration.
+pub fn index_members(
+    index: &mut FileIndex,
+    document: &[u8],
+    resolver: &NameResolver,
+    body: &Node,
+    class_fqn: &str,
+    url: &Url,
+) -> anyhow::Result<()> {
+    let mut cursor = body.walk();
+    for member in body.named_children(&mut cursor) {
+        let kind = match member.kind() {
+            "method_declaration" => ClassRecordKind::Method,
+            "enum_case" => ClassRecordKind::EnumCase,
+            "property_declaration" => {
+                index_properties(index, document, resolver, &member, class_fqn, url)?;
+                continue;
+            }
+            "const_declaration" => {
+                index_constants(index, document, &member, url, |name| {
+                    format!("{}::{}", class_fqn, name)
+                })?;
+                continue;
+            }
+            _ => continue,
+        };
+        let name_node = if let Some(name_node) = member.child_by_field_name("name") {
+            name_node
+        } else {
+            continue;
+        };
+        let name = name_node.utf8_text(document)?;
+        let fqn = format!("{}::{}", class_fqn, name);
+        log::debug!("member's FQN = {}", fqn);
+        let parameters = member
+            .child_by_field_name("parameters")
+            .map(|p| p.utf8_text(document).ok().map(|x| x.to_string()))
+            .flatten();
+        let return_type_node = member.child_by_field_name("return_type");
+        let return_type = return_type_node
+            .map(|rt| rt.utf8_text(document).ok().map(|x| x.to_string()))
+            .flatten();
+        let type_fqn = get_type_fqn(&member, return_type_node, "@return", document, resolver);
+        let record = ClassRecord {
+            id: 0,
+            fqn,
+            description: get_description(&member, document),
+            location: name_node.range().to_locaton(url),
+            range: member.range().to_locaton(url).range,
+            parameters,
+            attributes: get_modifiers(&member, document),
+            return_type,
+            type_fqn,
+            value: None,
+            kind,
+        };
+        index.save_row(record);
+        if name == "__construct" {
+            index_promoted_properties(index, document, resolver, &member, class_fqn, url)?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves every property of `public int $a = 1, $b;` sharing the type and modifiers.
+fn index_properties(
+    index: &mut FileIndex,
+    document: &[u8],
+    resolver: &NameResolver,
+    declaration: &Node,
+    class_fqn: &str,
+    url: &Url,
+) -> anyhow::Result<()> {
+    let type_node = declaration.child_by_field_name("type");
+    let property_type = type_node
+        .map(|t| t.utf8_text(document).ok().map(|x| x.to_string()))
+        .flatten();
+    let type_fqn = get_type_fqn(declaration, type_node, "@var", document, resolver);
+    let modifiers = get_modifiers(declaration, document);
+    let mut cursor = declaration.walk();
+    let elements: Vec<Node> = declaration
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "property_element")
+        .collect();
+    for element in elements.iter() {
+        let mut element_cursor = element.walk();
+        let name_node = match element
+            .named_children(&mut element_cursor)
+            .find(|n| n.kind() == "variable_name")
+        {
+            Some(name_node) => name_node,
+            None => continue,
+        };
+        // older grammars wrap the default value into `property_initializer`
+        let default_value = element.child_by_field_name("default_value").or_else(|| {
+            let mut element_cursor = element.walk();
+            let initializer = element
+                .named_children(&mut element_cursor)
+                .find(|n| n.kind() == "property_initializer");
+            initializer.and_then(|n| n.named_child(0))
+        });
+        // a declaration of several properties would nest them into each other in the outline
+        let range = if elements.len() == 1 {
+            declaration.range()
+        } else {
+            element.range()
+        };
+        let fqn = format!("{}::{}", class_fqn, name_node.utf8_text(document)?);
+        log::debug!("property's FQN = {}", fqn);
+        index.save_row(ClassRecord {
+            id: 0,
+            fqn,
+            description: get_description(declaration, document),
+            location: name_node.range().to_locaton(url),
+            range: range.to_locaton(url).range,
+            parameters: None,
+            attributes: modifiers.clone(),
+            return_type: property_type.clone(),
+            type_fqn: type_fqn.clone(),
+            value: default_value
+                .map(|v| v.utf8_text(document).ok().map(|x| x.to_string()))
+                .flatten(),
+            kind: ClassRecordKind::Property,
+        });
+    }
+    Ok(())
+}
+
+/// Saves every constant of `const A = 1, B = 2;` with its value, `fqn` builds FQN from the name.
+pub fn index_constants<F: Fn(&str) -> String>(
+    index: &mut FileIndex,
+    document: &[u8],
+    declaration: &Node,
+    url: &Url,
+    fqn: F,
+) -> anyhow::Result<()> {
+    let constant_type = declaration
+        .child_by_field_name("type")
+        .map(|t| t.utf8_text(document).ok().map(|x| x.to_string()))
+        .flatten();
+    let modifiers = get_modifiers(declaration, document);
+    let mut cursor = declaration.walk();
+    let elements: Vec<Node> = declaration
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "const_element")
+        .collect();
+    for element in elements.iter() {
+        let mut element_cursor = element.walk();
+        let name_node = match element
+            .named_children(&mut element_cursor)
+            .find(|n| n.kind() == "name")
+        {
+            Some(name_node) => name_node,
+            None => continue,
+        };
+        let range = if elements.len() == 1 {
+            declaration.range()
+        } else {
+            element.range()
+        };
+        let fqn = fqn(name_node.utf8_text(document)?);
+        log::debug!("constant's FQN = {}", fqn);
+        index.save_row(ClassRecord {
+            id: 0,
+            fqn,
+            description: get_description(declaration, document),
+            location: name_node.range().to_locaton(url),
+            range: range.to_locaton(url).range,
+            parameters: None,
+            attributes: modifiers.clone(),
+            return_type: constant_type.clone(),
+            type_fqn: None,
+            value: name_node
+                .next_named_sibling()
+                .map(|v| v.utf8_text(document).ok().map(|x| x.to_string()))
+                .flatten(),
+            kind: ClassRecordKind::Constant,
+        });
+    }
+    Ok(())
+}
+
+/// Saves properties declared by constructor parameters with a visibility (`public readonly int $id`).
+fn index_promoted_properties(
+    index: &mut FileIndex,
+    document: &[u8],
+    resolver: &NameResolver,
+    constructor: &Node,
+    class_fqn: &str,
+    url: &Url,
+) -> anyhow::Result<()> {
+    let parameters = match constructor.child_by_field_name("parameters") {
+        Some(parameters) => parameters,
+        None => return Ok(()),
+    };
+    let mut cursor = parameters.walk();
+    for parameter in parameters.named_children(&mut cursor) {
+        if parameter.kind() != "property_promotion_parameter" {
+            continue;
+        }
+        let name_node = match parameter.child_by_field_name("name") {
+            // `public &$x` keeps the variable inside `by_ref`
+            Some(n) if n.kind() == "by_ref" => match n.named_child(0) {
+                Some(n) => n,
+                None => continue,
+            },
+            Some(n) => n,
+            None => continue,
+        };
+        let field_text = |field: &str| {
+            parameter
+                .child_by_field_name(field)
+                .map(|n| n.utf8_text(document).ok().map(|x| x.to_string()))
+                .flatten()
+        };
+        let fqn = format!("{}::{}", class_fqn, name_node.utf8_text(document)?);
+        log::debug!("promoted property's FQN = {}", fqn);
+        index.save_row(ClassRecord {
+            id: 0,
+            fqn,
+            description: get_description(&parameter, document),
+            location: name_node.range().to_locaton(url),
+            range: parameter.range().to_locaton(url).range,
+            parameters: None,
+            attributes: get_modifiers(&parameter, document),
+            return_type: field_text("type"),
+            type_fqn: get_type_fqn(
+                &parameter,
+                parameter.child_by_field_name("type"),
+                "@var",
+                document,
+                resolver,
+            ),
+            value: field_text("default_value"),
+            kind: ClassRecordKind::Property,
+        });
+    }
+    Ok(())
+}
+
+/// Returns modifiers of a member as written: `public static`, `protected readonly`...
+fn get_modifiers(node: &Node, document: &[u8]) -> Option<String> {
+    let mut cursor = node.walk();
+    let modifiers: Vec<&str> = node
+        .children(&mut cursor)
+        .filter(|n| n.kind().ends_with("_modifier"))
+        .filter_map(|n| n.utf8_text(document).ok())
+        .collect();
+    if modifiers.is_empty() {
+        None
+    } else {
+        Some(modifiers.join(" "))
+    }
+}
+
+/// Resolves classes of the declared type for lookups of chained calls. The `tag` of the docblock
+/// (`@return`, `@var`) goes first: it can say `static`, `$this` or `Foo[]` where the type cannot.
+pub fn get_type_fqn(
+    declaration: &Node,
+    type_node: Option<Node>,
+    tag: &str,
+    document: &[u8],
+    resolver: &NameResolver,
+) -> Option<String> {
+    let docblock = declaration
+        .prev_sibling()
+        .filter(|c| c.kind() == "comment")
+        .and_then(|c| c.utf8_text(document).ok())
+        .unwrap_or_default();
+    let type_text = doc_tag_type(docblock, tag, None)
+        .or_else(|| type_node.and_then(|t| t.utf8_text(document).ok()))?;
+    // relative types depend on the class the member is looked up on
+    let types = class_types(type_text, |name| match name.to_lowercase().as_str() {
+        keyword @ ("self" | "static" | "$this" | "parent") => Some(keyword.to_string()),
+        _ => Some(resolver.resolve_class(name, declaration, document)),
+    });
+    Some(types.join("|")).filter(|x| !x.is_empty())
+}
 
-#[derive(Debug, Default)]
-pub struct EnumDeclarationIndexer {}
-#[derive(Debug, Default)]
-pub struct FunctionDeclarationIndexer {}
-#[derive(Debug, Default)]
-pub struct InterfaceDeclarationIndexer {}
-#[derive(Debug, Default)]
-pub struct TraitDeclarationIndexer {}
-#[derive(Debug, Default)]
-pub struct TraitUseClauseIndexer {}
-#[derive(Debug, Default)]
-pub struct ClassLikeReferenceIndexer {}
-#[derive(Debug, Default)]
-pub struct FunctionReferenceIndexer {}
-#[derive(Debug, Default)]
-pub struct ConstantDeclarationIndexer {}
-#[derive(Debug, Default)]
-pub struct MemberIndexer {}
-
-pub fn reindex_project<P: AsRef<Path> + Debug>(root_path: P) -> anyhow::Result<()> {
+pub fn reindex_project<P: AsRef<Path> + Debug>(
+    root_path: P,
+    config: &Config,
+) -> anyhow::Result<()> {
     log::info!("Start to reindex project {:?}", &root_path);
-    // get project path from init params
-    {
-        let mut db = Db::new(&root_path)?;
-        db.setup()?;
-        db.clean_index()?;
-        DB.set(Some(db));
+    let location = DbLocation::new(&root_path, config.database.as_deref())?;
+    open_index(&location)?;
+    reindex_folder(&root_path, config)
+}
+
+/// Opens the index shared by all workspace folders and makes it available through `DB`.
+pub fn open_index(location: &DbLocation) -> anyhow::Result<()> {
+    log::info!("Opening index {:?}", location);
+    let mut db = Db::open(location)?;
+    db.setup()?;
+    DB.set(Some(db));
+    Ok(())
+}
+
+/// Workspace folder indexed into the shared database.
+#[derive(Debug)]
+pub struct Folder {
+    pub path: PathBuf,
+    pub autoload: Option<Autoload>,
+    pub filter: FileFilter,
+}
+impl Folder {
+    /// Checks whether the file is one `reindex_folder` would pick:
+    /// allowed by the config and inside the autoload roots of composer projects.
+    fn indexes(&self, path: &Path, size: u64) -> bool {
+        let in_roots = self.autoload.as_ref().map_or(true, |autoload| {
+            autoload.roots().iter().any(|root| path.starts_with(root))
+        });
+        // the walker does not enter excluded directories
+        let excluded = path
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&self.path))
+            .any(|dir| self.filter.is_excluded(dir));
+        in_roots && !excluded && self.filter.is_indexed(path, size)
     }
+}
+
+fn innermost_folder<'a>(folders: &'a [Folder], path: &Path) -> Option<&'a Folder> {
+    folders
+        .iter()
+        .filter(|folder| path.starts_with(&folder.path))
+        .max_by_key(|folder| folder.path.as_os_str().len())
+}
+
+/// Returns the tag of the innermost workspace folder containing the path.
+pub fn folder_of<P: AsRef<Path>>(path: P) -> Option<String> {
+    FOLDERS.with_borrow(|folders| {
+        innermost_folder(folders, path.as_ref()).map(|folder| folder_tag(&folder.path))
+    })
+}
+fn folder_tag(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
 
-    // collect all PHP files
-    let files: Vec<_> = walkdir::WalkDir::new(&root_path)
-        .into_iter()
+/// Indexes new and changed files of the workspace folder and drops the removed ones.
+/// Files of nested workspace folders are left to those folders.
+pub fn reindex_folder<P: AsRef<Path> + Debug>(root_path: P, config: &Config) -> anyhow::Result<()> {
+    log::info!("Start to reindex folder {:?}", &root_path);
+    let root_path = root_path.as_ref().to_path_buf();
+    let tag = folder_tag(&root_path);
+    // composer projects are limited to autoloadable code
+    let autoload = Autoload::load(&root_path).unwrap_or_else(|e| {
+        // a broken composer.json should not leave the project unindexed
+        log::error!("I cannot read autoload rules of {:?}: {}", root_path, e);
+        None
+    });
+    let roots = match &autoload {
+        Some(autoload) => autoload.roots(),
+        None => vec![root_path.clone()],
+    };
+    let filter = config.file_filter(&root_path)?;
+    FOLDERS.with_borrow_mut(|folders| {
+        folders.retain(|folder| folder.path != root_path);
+        folders.push(Folder {
+            path: root_path.clone(),
+            autoload,
+            filter: filter.clone(),
+        });
+    });
+    // collect all PHP files allowed by the config
+    let files: BTreeSet<_> = roots
+        .iter()
+        .flat_map(|root| {
+            walkdir::WalkDir::new(root)
+                .into_iter()
+                .filter_entry(|e| !filter.is_excluded(e.path()))
+        })
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "php"))
+        .filter(|e| {
+            e.metadata().map_or(false, |metadata| {
+                filter.is_indexed(e.path(), metadata.len())
+            })
+        })
         .map(|file| file.path().to_str().unwrap().to_owned())
+        .filter(|path| folder_of(path).map_or(true, |folder| folder == tag))
         .collect();
-    // run indexers in parallel
-    files.iter().for_each(move |path| {
-        let mut parser = Parser::new();
-        parser
-            .set_language(tree_sitter_php::language_php())
-            .expect("Error loading PHP parsing support");
-        let contents = fs::read(&path).expect(format!("Cannot read path {}", &path).as_str());
-        let parsed = parser.parse(&contents, None);
-        let tree = if let Some(tree) = parsed {
-            tree
-        } else {
-            log::error!("I cannot parse {:?}", &path);
-            return;
-        };
-        let uri = Url::from_file_path(path).unwrap();
-        {
-            DB.with_borrow_mut(|db| {
-                if let Some(db) = db {
-                    INDEXERS.with_borrow_mut(|indexers| {
-                        for indexer in indexers {
-                            indexer.index(db, &contents, &tree, &uri);
-                        }
-                    });
+    DB.with_borrow_mut(|db| {
+        let db = db.as_mut().ok_or(anyhow!("Database is not initialized"))?;
+        let mut indexed_files = db.get_indexed_files(&tag)?;
+        let mut changed = vec![];
+        for path in files.iter() {
+            let uri = Url::from_file_path(path).map_err(|_| anyhow!("Invalid path {}", path))?;
+            // the file can be removed or become unreadable after the walk;
+            // it stays in `indexed_files`, so its rows are dropped below
+            let stamp = match FileStamp::new(path) {
+                std::result::Result::Ok(stamp) => stamp,
+                Err(e) => {
+                    log::warn!("I cannot read {:?}, skipping it: {}", path, e);
+                    continue;
                 }
-            });
+            };
+            // skip files that did not change since the last run
+            if indexed_files.remove(uri.as_str()) != Some(stamp) {
+                changed.push(path.clone());
+            }
+        }
+        let reindexed = changed.len();
+        // parse files on the worker pool while this thread is the only one writing to SQLite
+        let (sender, receiver) = crossbeam_channel::bounded::<FileIndex>(WRITE_BATCH_SIZE);
+        std::thread::spawn(move || {
+            changed
+                .into_par_iter()
+                .for_each_with(sender, |sender, path| match index_path(&path) {
+                    std::result::Result::Ok(file) => {
+                        let _ = sender.send(file);
+                    }
+                    Err(e) => log::error!("I cannot index {:?}: {}", &path, e),
+                });
+        });
+        let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+        for mut file in receiver {
+            file.folder = Some(tag.clone());
+            batch.push(file);
+            if batch.len() >= WRITE_BATCH_SIZE {
+                db.save_file_indexes(&batch)?;
+                batch.clear();
+            }
+        }
+        db.save_file_indexes(&batch)?;
+        // files removed since the last run
+        for uri in indexed_files.keys() {
+            db.delete_file(uri)?;
+        }
+        log::info!(
+            "Reindexed {} of {} files, removed {}",
+            reindexed,
+            files.len(),
+            indexed_files.len()
+        );
+        Ok(())
+    })
+}
+
+/// Drops files of the workspace folder from the index.
+/// Files another open folder contains are handed over to it instead.
+pub fn remove_folder<P: AsRef<Path> + Debug>(db: &mut Db, root_path: P) -> anyhow::Result<()> {
+    log::info!("Removing folder {:?} from the index", &root_path);
+    FOLDERS.with_borrow_mut(|folders| {
+        folders.retain(|folder| folder.path != root_path.as_ref());
+    });
+    let tag = folder_tag(root_path.as_ref());
+    for uri in db.get_indexed_files(&tag)?.into_keys() {
+        let path = match Url::parse(&uri)
+            .ok()
+            .and_then(|uri| uri.to_file_path().ok())
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        // the removed folder was nested in another one;
+        // the file is left tagged and dropped below if it cannot be reindexed
+        if folder_of(&path).is_some() {
+            if let Err(e) = reindex_path(db, &path) {
+                log::warn!("I cannot reindex {:?}, dropping it: {}", &path, e);
+            }
         }
-        //{
-        //    let mut cursor = tree.walk();
-        //    cursor.goto_first_child();
-        //    loop {
-        //        let node = cursor.node();
-        //        DB.with_borrow_mut(|db| {
-        //            if let Some(db) = db {
-        //                parse_node(&node, db, 0, &contents, &uri);
-        //            }
-        //        });
-        //        if !cursor.goto_next_sibling() {
-        //            break;
-        //        }
-        //    }
-        //}
+    }
+    db.delete_folder(&tag)?;
+    Ok(())
+}
+
+/// Modification time and size used to detect changed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub mtime: i64,
+    pub size: i64,
+}
+impl FileStamp {
+    pub fn new<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos() as i64;
+        Ok(Self {
+            mtime,
+            size: metadata.len() as i64,
+        })
+    }
+}
+
+/// Indexes the file the autoloader would load the class-like symbol
+/// (or the class of the member) from. Returns `true` if any file was indexed.
+pub fn index_autoloaded(db: &mut Db, fqn: &str) -> bool {
+    let class_fqn = fqn.split("::").next().unwrap_or(fqn);
+    let candidates: Vec<_> = FOLDERS.with_borrow(|folders| {
+        folders
+            .iter()
+            .filter_map(|folder| folder.autoload.as_ref())
+            .flat_map(|autoload| autoload.resolve(class_fqn))
+            .collect()
     });
+    let mut indexed = false;
+    for path in candidates.iter().filter(|path| path.is_file()) {
+        log::debug!("Indexing autoloaded file {:?}", path);
+        match reindex_path(db, path) {
+            std::result::Result::Ok(()) => indexed = true,
+            Err(e) => log::error!("I cannot index {:?}: {}", path, e),
+        }
+    }
+    indexed
+}
+
+/// Reads the file from disk and replaces its rows in the index.
+/// A file its workspace folder does not index is dropped from the index instead.
+pub fn reindex_path<P: AsRef<Path> + Debug>(db: &mut Db, path: P) -> anyhow::Result<()> {
+    let size = fs::metadata(&path)?.len();
+    let (folder, indexed) =
+        FOLDERS.with_borrow(|folders| match innermost_folder(folders, path.as_ref()) {
+            Some(folder) => (
+                Some(folder_tag(&folder.path)),
+                folder.indexes(path.as_ref(), size),
+            ),
+            None => (None, true),
+        });
+    if !indexed {
+        log::debug!("{:?} is not indexed by its folder, skipping it", &path);
+        let uri = Url::from_file_path(&path).map_err(|_| anyhow!("Invalid path {:?}", &path))?;
+        db.delete_file(uri.as_str())?;
+        return Ok(());
+    }
+    let mut file = index_path(&path)?;
+    file.folder = folder;
+    db.save_file_indexes(&[file])?;
     Ok(())
 }
+
+/// Reads the file from disk and runs indexers against it.
+pub fn index_path<P: AsRef<Path> + Debug>(path: P) -> anyhow::Result<FileIndex> {
+    let stamp = FileStamp::new(&path)?;
+    let contents = fs::read(&path)?;
+    let uri = Url::from_file_path(&path).map_err(|_| anyhow!("Invalid path {:?}", &path))?;
+    let mut file = index_file(&contents, &uri)?;
+    file.stamp = Some(stamp);
+    Ok(file)
+}
+
+/// Parses the document and collects everything indexers extract from it.
+/// Does not touch the database, so it is safe to call from worker threads.
+pub fn index_file(contents: &[u8], uri: &Url) -> anyhow::Result<FileIndex> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(tree_sitter_php::language_php())
+        .expect("Error loading PHP parsing support");
+    let tree = parser
+        .parse(contents, None)
+        .ok_or(anyhow!("I cannot parse {}", uri))?;
+    Ok(index_tree(contents, &tree, uri))
+}
+
+/// Runs indexers against an already parsed document.
+pub fn index_tree(contents: &[u8], tree: &Tree, uri: &Url) -> FileIndex {
+    let mut file = index_declarations(contents, tree, uri);
+    INDEXERS.with_borrow_mut(|indexers| run_indexers(indexers, &mut file, contents, tree, uri));
+    file
+}
+
+/// Collects only the declarations of an already parsed document, references are skipped.
+pub fn index_declarations(contents: &[u8], tree: &Tree, uri: &Url) -> FileIndex {
+    let mut file = FileIndex::new(uri);
+    DECLARATION_INDEXERS
+        .with_borrow_mut(|indexers| run_indexers(indexers, &mut file, contents, tree, uri));
+    file
+}
+fn run_indexers(
+    indexers: &mut [Box<dyn Indexer>],
+    file: &mut FileIndex,
+    contents: &[u8],
+    tree: &Tree,
+    uri: &Url,
+) {
+    for indexer in indexers {
+        if let Err(e) = indexer.index(file, contents, tree, uri) {
+            log::error!("Indexer failed on {}: {}", uri, e);
+        }
+    }
+}
 fn parse_node<'a>(
     node: &'a tree_sitter::Node<'a>,
-    index: &mut Db,
+    index: &mut FileIndex,
     lvl: usize,
     contents: &[u8],
     uri: &Url,
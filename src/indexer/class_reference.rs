@@ -0,0 +1,88 @@
+use lsp_types::Url;
+use tree_sitter::Node;
+
+use crate::{
+    db::{ClassRecordKind, ReferenceRecord},
+    ToLocation,
+};
+
+use super::{
+    index::{self, FileIndex},
+    name_resolver::NameResolver,
+};
+
+#[derive(Debug, Default)]
+pub struct ClassLikeReferenceIndexer {}
+impl index::Indexer for ClassLikeReferenceIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let resolver = NameResolver::new(tree, document);
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            if is_class_reference(&node, document) {
+                let name = node.utf8_text(document)?;
+                let record = ReferenceRecord {
+                    id: 0,
+                    fqn: resolver.resolve_class(name, &node, document),
+                    fallback_fqn: None,
+                    kind: ClassRecordKind::Class,
+                    location: node.range().to_locaton(url),
+                };
+                index.save_reference(record);
+            } else if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Checks if the node is a name of a class-like symbol used in
+/// `new`, static calls, constants, type hints, `instanceof`, `extends`/`implements`,
+/// trait `use` and `catch`.
+pub fn is_class_reference(node: &Node, document: &[u8]) -> bool {
+    if !matches!(node.kind(), "name" | "qualified_name") {
+        return false;
+    }
+    let parent = if let Some(parent) = node.parent() {
+        parent
+    } else {
+        return false;
+    };
+    let is_reference = match parent.kind() {
+        "object_creation_expression"
+        | "named_type"
+        | "base_clause"
+        | "class_interface_clause"
+        | "use_declaration"
+        | "type_list"
+        | "catch_clause" => true,
+        "scoped_call_expression" | "scoped_property_access_expression" => {
+            parent.child_by_field_name("scope") == Some(*node)
+        }
+        "class_constant_access_expression" => parent.named_child(0) == Some(*node),
+        "binary_expression" => {
+            let mut cursor = parent.walk();
+            let is_instanceof = parent
+                .children(&mut cursor)
+                .any(|child| child.kind() == "instanceof");
+            is_instanceof && parent.child_by_field_name("right") == Some(*node)
+        }
+        _ => false,
+    };
+    // relative class names point to the current class, not to a class named so
+    is_reference
+        && !node.utf8_text(document).map_or(true, |name| {
+            matches!(name.to_lowercase().as_str(), "self" | "static" | "parent")
+        })
+}
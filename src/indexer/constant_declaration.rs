@@ -0,0 +1,87 @@
+use lsp_types::Url;
+use tree_sitter::{Query, QueryCursor};
+use tree_sitter_php::language_php;
+
+use crate::{
+    db::{ClassRecord, ClassRecordKind},
+    ToLocation,
+};
+
+use super::{
+    get_class_fqn, get_description, get_namespace,
+    index::{self, FileIndex},
+    index_constants,
+    name_resolver::to_fqn,
+};
+
+/// Indexes top-level `const` statements and `define()` calls.
+/// Class constants are saved together with the other members of their class.
+#[derive(Debug, Default)]
+pub struct ConstantDeclarationIndexer {}
+impl index::Indexer for ConstantDeclarationIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let root_node = tree.root_node();
+        let query = Query::new(language_php(), "(const_declaration) @const")?;
+        let mut query_cursor = QueryCursor::new();
+        for m in query_cursor.matches(&query, root_node, &document[..]) {
+            let declaration = m.captures[0].node;
+            if get_class_fqn(&declaration, document).is_some() {
+                continue;
+            }
+            let namespace = get_namespace(&declaration, document);
+            index_constants(index, document, &declaration, url, |name| {
+                to_fqn(&format!("{}\\{}", namespace, name))
+            })?;
+        }
+
+        let query = Query::new(
+            language_php(),
+            "(function_call_expression
+               function: (name) @function
+               arguments: (arguments . (argument) @name . (argument) @value)
+            ) @call",
+        )?;
+        let mut query_cursor = QueryCursor::new();
+        for m in query_cursor.matches(&query, root_node, &document[..]) {
+            let call_node = m.captures[0].node;
+            if !m.captures[1]
+                .node
+                .utf8_text(document)?
+                .eq_ignore_ascii_case("define")
+            {
+                continue;
+            }
+            // only literal names can be resolved without running the code
+            let name_node = match m.captures[2].node.named_child(0) {
+                Some(n) if n.kind().ends_with("string") => n,
+                _ => continue,
+            };
+            let name = name_node
+                .utf8_text(document)?
+                .trim_matches(|c| c == '\'' || c == '"');
+            // `define()` takes fully qualified names, the current namespace does not apply
+            let fqn = to_fqn(name);
+            log::debug!("defined constant's FQN = {}", fqn);
+            index.save_row(ClassRecord {
+                id: 0,
+                fqn,
+                description: get_description(&call_node.parent().unwrap_or(call_node), document),
+                location: name_node.range().to_locaton(url),
+                range: call_node.range().to_locaton(url).range,
+                parameters: None,
+                attributes: None,
+                return_type: None,
+                type_fqn: None,
+                value: Some(m.captures[3].node.utf8_text(document)?.to_string()),
+                kind: ClassRecordKind::Constant,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use lsp_types::Url;
+use tree_sitter::{Query, QueryCursor};
+use tree_sitter_php::language_php;
+
+use crate::db::ClassRecordKind;
+
+use super::{
+    index::{self, FileIndex},
+    index_members,
+    name_resolver::NameResolver,
+    save_class_like,
+};
+
+const NODE_ID: &'static str = "enum_declaration";
+#[derive(Debug, Default)]
+pub struct EnumDeclarationIndexer {}
+impl index::Indexer for EnumDeclarationIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let root_node = tree.root_node();
+        let query = format!("({NODE_ID} body: (enum_declaration_list) @body) @enum");
+        let query = Query::new(language_php(), &query)?;
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(&query, root_node, &document[..]);
+        let resolver = NameResolver::new(tree, document);
+        for m in matches {
+            let enum_node = m.captures[0].node;
+            let body_node = m.captures[1].node;
+            let enum_fqn =
+                save_class_like(index, document, &enum_node, ClassRecordKind::Enum, url)?;
+            index_members(index, document, &resolver, &body_node, &enum_fqn, url)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use lsp_types::Url;
+use tree_sitter::{Query, QueryCursor};
+use tree_sitter_php::language_php;
+
+use crate::{
+    db::{ClassRecord, ClassRecordKind},
+    ToLocation,
+};
+
+use super::{
+    get_description, get_namespace, get_type_fqn,
+    index::{self, FileIndex},
+    name_resolver::NameResolver,
+};
+
+const NODE_ID: &'static str = "function_definition";
+#[derive(Debug, Default)]
+pub struct FunctionDeclarationIndexer {}
+impl index::Indexer for FunctionDeclarationIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let root_node = tree.root_node();
+        let query = format!(
+            "({NODE_ID}
+               name: (name) @function_name
+               parameters: (formal_parameters) @params
+               return_type: (_)? @return_type
+) @function"
+        );
+        let query = Query::new(language_php(), &query)?;
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(&query, root_node, &document[..]);
+        let resolver = NameResolver::new(tree, document);
+        for m in matches {
+            let function_node = m.captures[0].node;
+            let function_name = m.captures[1].node.utf8_text(&document)?;
+            let function_params = m.captures[2].node.utf8_text(&document)?;
+            let namespace = get_namespace(&function_node, document);
+            let fqn = format!("{}\\{}", namespace, function_name);
+            log::debug!("function's FQN = {}", fqn);
+            let return_type = m
+                .captures
+                .get(3)
+                .map(|rt| rt.node.utf8_text(&document).ok().map(|x| x.to_string()))
+                .flatten();
+            let type_fqn = get_type_fqn(
+                &function_node,
+                m.captures.get(3).map(|rt| rt.node),
+                "@return",
+                document,
+                &resolver,
+            );
+
+            let record = ClassRecord {
+                id: 0,
+                fqn,
+                description: get_description(&function_node, document),
+                location: m.captures[1].node.range().to_locaton(url),
+                range: function_node.range().to_locaton(url).range,
+                parameters: Some(function_params.into()),
+                attributes: None,
+                value: None,
+                return_type,
+                type_fqn,
+                kind: ClassRecordKind::Function,
+            };
+            index.save_row(record);
+        }
+        Ok(())
+    }
+}
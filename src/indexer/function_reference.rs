@@ -0,0 +1,58 @@
+use lsp_types::Url;
+
+use crate::{
+    db::{ClassRecordKind, ReferenceRecord},
+    ToLocation,
+};
+
+use super::{
+    index::{self, FileIndex},
+    name_resolver::NameResolver,
+};
+
+const NODE_ID: &'static str = "function_call_expression";
+#[derive(Debug, Default)]
+pub struct FunctionReferenceIndexer {}
+impl index::Indexer for FunctionReferenceIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let resolver = NameResolver::new(tree, document);
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            let function_node = if node.kind() == NODE_ID {
+                node.child_by_field_name("function")
+                    .filter(|f| matches!(f.kind(), "name" | "qualified_name"))
+            } else {
+                None
+            };
+            if let Some(function_node) = function_node {
+                let name = function_node.utf8_text(document)?;
+                let candidates = resolver.resolve_function(name, &function_node, document);
+                // PHP falls back to the global function if the namespaced one is not declared,
+                // which is only known once the whole project is indexed
+                let record = ReferenceRecord {
+                    id: 0,
+                    fqn: candidates[0].clone(),
+                    fallback_fqn: candidates.get(1).cloned(),
+                    kind: ClassRecordKind::Function,
+                    location: function_node.range().to_locaton(url),
+                };
+                index.save_reference(record);
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -1,10 +1,48 @@
 use lsp_types::Url;
 use tree_sitter::Tree;
 
-use crate::db::Db;
+use crate::db::{ClassRecord, InheritanceRecord, ReferenceRecord};
+
+use super::FileStamp;
 
 pub trait Indexer {
-    fn index(&self, index: &mut Db, document: &[u8], tree: &Tree, url: &Url) -> anyhow::Result<()>;
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &Tree,
+        url: &Url,
+    ) -> anyhow::Result<()>;
+}
+
+/// Declarations and references extracted from one file.
+/// Indexers fill it on worker threads and the writer stores it in one transaction.
+#[derive(Debug, Default)]
+pub struct FileIndex {
+    pub uri: String,
+    pub stamp: Option<FileStamp>,
+    /// Workspace folder the file belongs to; the stored one is kept if it is `None`.
+    pub folder: Option<String>,
+    pub declarations: Vec<ClassRecord>,
+    pub references: Vec<ReferenceRecord>,
+    pub inheritance: Vec<InheritanceRecord>,
+}
+impl FileIndex {
+    pub fn new(uri: &Url) -> Self {
+        Self {
+            uri: uri.to_string(),
+            ..Default::default()
+        }
+    }
+    pub fn save_row(&mut self, record: ClassRecord) {
+        self.declarations.push(record);
+    }
+    pub fn save_reference(&mut self, reference: ReferenceRecord) {
+        self.references.push(reference);
+    }
+    pub fn save_inheritance(&mut self, edge: InheritanceRecord) {
+        self.inheritance.push(edge);
+    }
 }
 
 pub trait Index {
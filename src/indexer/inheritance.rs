@@ -0,0 +1,88 @@
+use lsp_types::Url;
+use tree_sitter::{Node, Query, QueryCursor};
+use tree_sitter_php::language_php;
+
+use crate::{
+    db::{InheritanceKind, InheritanceRecord},
+    ToLocation,
+};
+
+use super::{
+    get_namespace,
+    index::{self, FileIndex},
+    name_resolver::NameResolver,
+};
+
+/// Saves `extends` and `implements` clauses and trait `use` of class-like declarations.
+#[derive(Debug, Default)]
+pub struct InheritanceIndexer {}
+impl index::Indexer for InheritanceIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let resolver = NameResolver::new(tree, document);
+        let query = Query::new(
+            language_php(),
+            "[
+               (class_declaration)
+               (interface_declaration)
+               (trait_declaration)
+               (enum_declaration)
+            ] @class",
+        )?;
+        let mut query_cursor = QueryCursor::new();
+        for m in query_cursor.matches(&query, tree.root_node(), &document[..]) {
+            let class_node = m.captures[0].node;
+            let name = match class_node.child_by_field_name("name") {
+                Some(name_node) => name_node.utf8_text(document)?,
+                None => continue,
+            };
+            let fqn = format!("{}\\{}", get_namespace(&class_node, document), name);
+            let mut clauses = vec![];
+            let mut cursor = class_node.walk();
+            for child in class_node.named_children(&mut cursor) {
+                match child.kind() {
+                    // interfaces extend other interfaces with `base_clause` too
+                    "base_clause" => clauses.push((child, InheritanceKind::Extends)),
+                    "class_interface_clause" => clauses.push((child, InheritanceKind::Implements)),
+                    _ => (),
+                }
+            }
+            if let Some(body) = class_node.child_by_field_name("body") {
+                let mut cursor = body.walk();
+                for member in body.named_children(&mut cursor) {
+                    if member.kind() == "use_declaration" {
+                        clauses.push((member, InheritanceKind::UsesTrait));
+                    }
+                }
+            }
+            for (clause, kind) in clauses {
+                for parent in parent_names(&clause) {
+                    let parent_name = parent.utf8_text(document)?;
+                    index.save_inheritance(InheritanceRecord {
+                        id: 0,
+                        fqn: fqn.clone(),
+                        parent_fqn: resolver.resolve_class(parent_name, &parent, document),
+                        kind,
+                        location: parent.range().to_locaton(url),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Names listed in the clause, the `{ ... }` conflict resolution of trait `use` is skipped.
+fn parent_names<'a>(clause: &Node<'a>) -> Vec<Node<'a>> {
+    let mut cursor = clause.walk();
+    let names = clause
+        .named_children(&mut cursor)
+        .filter(|n| matches!(n.kind(), "name" | "qualified_name"))
+        .collect();
+    names
+}
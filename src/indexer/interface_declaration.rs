@@ -0,0 +1,45 @@
+use lsp_types::Url;
+use tree_sitter::{Query, QueryCursor};
+use tree_sitter_php::language_php;
+
+use crate::db::ClassRecordKind;
+
+use super::{
+    index::{self, FileIndex},
+    index_members,
+    name_resolver::NameResolver,
+    save_class_like,
+};
+
+const NODE_ID: &'static str = "interface_declaration";
+#[derive(Debug, Default)]
+pub struct InterfaceDeclarationIndexer {}
+impl index::Indexer for InterfaceDeclarationIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let root_node = tree.root_node();
+        let query = format!("({NODE_ID} body: (declaration_list) @body) @interface");
+        let query = Query::new(language_php(), &query)?;
+        let mut query_cursor = QueryCursor::new();
+        let matches = query_cursor.matches(&query, root_node, &document[..]);
+        let resolver = NameResolver::new(tree, document);
+        for m in matches {
+            let interface_node = m.captures[0].node;
+            let body_node = m.captures[1].node;
+            let interface_fqn = save_class_like(
+                index,
+                document,
+                &interface_node,
+                ClassRecordKind::Interface,
+                url,
+            )?;
+            index_members(index, document, &resolver, &body_node, &interface_fqn, url)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use lsp_types::Url;
+
+use crate::{
+    db::{ClassRecordKind, ReferenceRecord},
+    inference::TypeInference,
+    ToLocation,
+};
+
+use super::index::{self, FileIndex};
+
+#[derive(Debug, Default)]
+pub struct MethodReferenceIndexer {}
+impl index::Indexer for MethodReferenceIndexer {
+    fn index(
+        &self,
+        index: &mut FileIndex,
+        document: &[u8],
+        tree: &tree_sitter::Tree,
+        url: &Url,
+    ) -> anyhow::Result<()> {
+        let inference = TypeInference::local(tree, document);
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            if matches!(
+                node.kind(),
+                "member_call_expression"
+                    | "nullsafe_member_call_expression"
+                    | "scoped_call_expression"
+            ) {
+                if let Some(name_node) = node
+                    .child_by_field_name("name")
+                    .filter(|n| n.kind() == "name")
+                {
+                    let method_name = name_node.utf8_text(document)?;
+                    let types = if node.kind() == "scoped_call_expression" {
+                        node.child_by_field_name("scope")
+                            .map_or(vec![], |scope| inference.scope_types(&scope))
+                    } else {
+                        node.child_by_field_name("object")
+                            .map_or(vec![], |object| inference.expression_types(&object))
+                    };
+                    let class_fqn = types.into_iter().find(|x| !x.ends_with("[]"));
+                    // the class stays empty if we do not know the type of the object
+                    let record = ReferenceRecord {
+                        id: 0,
+                        fqn: format!("{}::{}", class_fqn.unwrap_or_default(), method_name),
+                        fallback_fqn: None,
+                        kind: ClassRecordKind::Method,
+                        location: name_node.range().to_locaton(url),
+                    };
+                    index.save_reference(record);
+                }
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use tree_sitter::{Node, Tree};
+
+use super::get_namespace;
+
+/// Maps names used in a document to FQNs using `use` imports and the current namespace.
+#[derive(Debug, Default)]
+pub struct NameResolver {
+    classes: HashMap<String, String>,
+    functions: HashMap<String, String>,
+    constants: HashMap<String, String>,
+}
+impl NameResolver {
+    pub fn new(tree: &Tree, document: &[u8]) -> Self {
+        let mut resolver = Self::default();
+        let mut cursor = tree.walk();
+        loop {
+            let node = cursor.node();
+            if node.kind() == "namespace_use_declaration" {
+                resolver.add_use_declaration(&node, document);
+            } else if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return resolver;
+                }
+            }
+        }
+    }
+    fn add_use_declaration(&mut self, declaration: &Node, document: &[u8]) {
+        let declaration_type = use_type(declaration);
+        // group use: `use App\Models\{User, Post}`
+        let mut cursor = declaration.walk();
+        let prefix = declaration
+            .named_children(&mut cursor)
+            .find(|n| n.kind() == "namespace_name")
+            .map(|n| n.utf8_text(document).ok())
+            .flatten();
+        let mut clauses = vec![];
+        let mut stack = vec![*declaration];
+        while let Some(node) = stack.pop() {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                match child.kind() {
+                    "namespace_use_clause" | "namespace_use_group_clause" => clauses.push(child),
+                    "namespace_use_group" => stack.push(child),
+                    _ => (),
+                }
+            }
+        }
+        for clause in clauses {
+            let mut name = None;
+            let mut alias = None;
+            let mut seen_as = false;
+            let mut cursor = clause.walk();
+            for child in clause.children(&mut cursor) {
+                match child.kind() {
+                    "as" => seen_as = true,
+                    "namespace_aliasing_clause" => {
+                        let mut cursor = child.walk();
+                        alias = child
+                            .named_children(&mut cursor)
+                            .find(|n| n.kind() == "name")
+                            .map(|n| n.utf8_text(document).ok())
+                            .flatten();
+                    }
+                    "qualified_name" | "namespace_name" | "name" => {
+                        let text = child.utf8_text(document).ok();
+                        if seen_as {
+                            alias = text;
+                        } else if name.is_none() {
+                            name = text;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            let name = match name {
+                Some(name) => name.trim_start_matches('\\'),
+                None => continue,
+            };
+            let fqn = match prefix {
+                Some(prefix) => format!("{}\\{}", prefix.trim_start_matches('\\'), name),
+                None => name.to_string(),
+            };
+            let key = alias
+                .unwrap_or_else(|| fqn.rsplit_once('\\').map_or(fqn.as_str(), |(_, x)| x))
+                .to_string();
+            let imports = match use_type(&clause).or(declaration_type) {
+                Some("function") => &mut self.functions,
+                Some("const") => &mut self.constants,
+                _ => &mut self.classes,
+            };
+            imports.insert(key, to_fqn(&fqn));
+        }
+    }
+    /// Resolves a class-like name used at the node position.
+    pub fn resolve_class(&self, name: &str, node: &Node, document: &[u8]) -> String {
+        if name.starts_with('\\') {
+            return to_fqn(name);
+        }
+        // `namespace\Foo` is relative to the current namespace, imports do not apply
+        if let Some(relative) = name
+            .get(..10)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("namespace\\"))
+            .map(|_| &name[10..])
+        {
+            return to_fqn(&format!("{}\\{}", get_namespace(node, document), relative));
+        }
+        let (first, rest) = match name.split_once('\\') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (name, None),
+        };
+        if let Some(import) = self.classes.get(first) {
+            return match rest {
+                Some(rest) => to_fqn(&format!("{}\\{}", import, rest)),
+                None => import.clone(),
+            };
+        }
+        to_fqn(&format!("{}\\{}", get_namespace(node, document), name))
+    }
+    /// Resolves a function name used at the node position.
+    /// Returns the namespaced candidate first and the global fallback after it.
+    pub fn resolve_function(&self, name: &str, node: &Node, document: &[u8]) -> Vec<String> {
+        self.resolve_global(&self.functions, name, node, document)
+    }
+    /// Resolves a constant name used at the node position, the same way as functions.
+    pub fn resolve_constant(&self, name: &str, node: &Node, document: &[u8]) -> Vec<String> {
+        self.resolve_global(&self.constants, name, node, document)
+    }
+    fn resolve_global(
+        &self,
+        imports: &HashMap<String, String>,
+        name: &str,
+        node: &Node,
+        document: &[u8],
+    ) -> Vec<String> {
+        if name.starts_with('\\') {
+            return vec![to_fqn(name)];
+        }
+        if let Some(import) = imports.get(name) {
+            return vec![import.clone()];
+        }
+        if name.contains('\\') {
+            return vec![self.resolve_class(name, node, document)];
+        }
+        let namespace = get_namespace(node, document);
+        if namespace.is_empty() {
+            vec![to_fqn(name)]
+        } else {
+            vec![to_fqn(&format!("{}\\{}", namespace, name)), to_fqn(name)]
+        }
+    }
+}
+fn use_type(node: &Node) -> Option<&'static str> {
+    let mut cursor = node.walk();
+    let use_type = node
+        .children(&mut cursor)
+        .map(|n| n.kind())
+        .find(|&kind| kind == "function" || kind == "const");
+    use_type
+}
+
+/// Converts a fully qualified name to the form the index stores it in:
+/// namespaced names have no leading backslash while global ones keep it.
+pub fn to_fqn(name: &str) -> String {
+    let name = name.trim_start_matches('\\');
+    if name.contains('\\') {
+        name.to_string()
+    } else {
+        format!("\\{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse;
+
+    const SOURCE: &str = r#"<?php
+namespace App\Http;
+
+use App\Models\User;
+use App\Models\Post as Article;
+use App\Support\{Str, Arr as Arrays};
+use App\Mixed\{Widget, function render, const LIMIT};
+use function App\Helpers\format_date;
+use function App\Helpers\{slug};
+use const App\Config\VERSION;
+use Vendor\Lib;
+
+echo 1;
+"#;
+
+    /// Runs the check against the last statement of the source.
+    fn with_resolver(source: &str, check: impl FnOnce(&NameResolver, &Node)) {
+        let tree = parse(source.as_bytes()).unwrap();
+        let resolver = NameResolver::new(&tree, source.as_bytes());
+        let offset = source.rfind("echo").unwrap();
+        let node = tree
+            .root_node()
+            .descendant_for_byte_range(offset, offset)
+            .unwrap();
+        check(&resolver, &node);
+    }
+
+    #[test]
+    fn classes_are_resolved_through_imports() {
+        with_resolver(SOURCE, |resolver, node| {
+            let resolve = |name| resolver.resolve_class(name, node, SOURCE.as_bytes());
+            assert_eq!(resolve("User"), "App\\Models\\User");
+            assert_eq!(resolve("Article"), "App\\Models\\Post");
+            assert_eq!(resolve("Str"), "App\\Support\\Str");
+            assert_eq!(resolve("Arrays"), "App\\Support\\Arr");
+            assert_eq!(resolve("Widget"), "App\\Mixed\\Widget");
+            assert_eq!(resolve("Lib\\Client"), "Vendor\\Lib\\Client");
+        });
+    }
+
+    #[test]
+    fn classes_are_resolved_against_the_namespace() {
+        with_resolver(SOURCE, |resolver, node| {
+            let resolve = |name| resolver.resolve_class(name, node, SOURCE.as_bytes());
+            assert_eq!(resolve("Request"), "App\\Http\\Request");
+            assert_eq!(resolve("Sub\\Thing"), "App\\Http\\Sub\\Thing");
+            assert_eq!(resolve("namespace\\User"), "App\\Http\\User");
+            assert_eq!(resolve("\\DateTime"), "\\DateTime");
+            assert_eq!(resolve("\\Foo\\Bar"), "Foo\\Bar");
+        });
+    }
+
+    #[test]
+    fn functions_and_constants_fall_back_to_global() {
+        with_resolver(SOURCE, |resolver, node| {
+            let function = |name| resolver.resolve_function(name, node, SOURCE.as_bytes());
+            let constant = |name| resolver.resolve_constant(name, node, SOURCE.as_bytes());
+            assert_eq!(function("format_date"), ["App\\Helpers\\format_date"]);
+            assert_eq!(function("slug"), ["App\\Helpers\\slug"]);
+            assert_eq!(function("render"), ["App\\Mixed\\render"]);
+            assert_eq!(function("strlen"), ["App\\Http\\strlen", "\\strlen"]);
+            assert_eq!(function("\\strlen"), ["\\strlen"]);
+            assert_eq!(constant("VERSION"), ["App\\Config\\VERSION"]);
+            assert_eq!(constant("LIMIT"), ["App\\Mixed\\LIMIT"]);
+            assert_eq!(constant("PHP_EOL"), ["App\\Http\\PHP_EOL", "\\PHP_EOL"]);
+        });
+    }
+
+    #[test]
+    fn global_code_has_no_fallback() {
+        let source = "<?php\nuse Foo\\Bar;\necho 1;\n";
+        with_resolver(source, |resolver, node| {
+            assert_eq!(
+                resolver.resolve_class("Baz", node, source.as_bytes()),
+                "\\Baz"
+            );
+            assert_eq!(
+                resolver.resolve_class("Bar", node, source.as_bytes()),
+                "Foo\\Bar"
+            );
+            assert_eq!(
+                resolver.resolve_function("strlen", node, source.as_bytes()),
+                ["\\strlen"]
+            );
+        });
+    }
+
+    #[test]
+    fn to_fqn_keeps_the_backslash_of_global_names() {
+        assert_eq!(to_fqn("\\App\\User"), "App\\User");
+        assert_eq!(to_fqn("User"), "\\User");
+        assert_eq!(to_fqn("\\User"), "\\User");
+    }
+}
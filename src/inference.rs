@@ -0,0 +1,808 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use tree_sitter::{Node, Point, Tree};
+
+use crate::{
+    db::{ClassRecord, ClassRecordKind, InheritanceKind},
+    indexer::{get_class_fqn, name_resolver::NameResolver},
+    DB,
+};
+
+/// Nodes with their own variables. Arrow functions see the variables of the enclosing scope,
+/// closures only the ones listed in `use`.
+const SCOPES: &[&str] = &[
+    "function_definition",
+    "method_declaration",
+    "anonymous_function",
+    "anonymous_function_creation_expression",
+    "arrow_function",
+];
+/// Declarations whose bodies do not belong to the scope around them.
+const DECLARATIONS: &[&str] = &[
+    "class_declaration",
+    "interface_declaration",
+    "trait_declaration",
+    "enum_declaration",
+    "anonymous_class",
+];
+
+/// Infers classes of variables in a document. Every function has its own variables typed by
+/// parameter hints and PHPDoc, assignments in the order they happen, `instanceof` checks,
+/// `catch` clauses, `foreach` over typed arrays and the enclosing scope for closures.
+/// Calls and property reads are typed by the declarations in the index, so fluent chains
+/// like `$repo->query()->where()->first()` resolve through the return types.
+///
+/// Types are FQNs in the form the index stores them, `Foo[]` stands for an array of `Foo`.
+pub struct TypeInference<'a> {
+    tree: &'a Tree,
+    document: &'a [u8],
+    resolver: NameResolver,
+    /// Whether calls and property reads are typed by the index.
+    use_index: bool,
+    /// Facts of a variable by the scope node id, a scope is walked once for all its lookups.
+    facts: RefCell<HashMap<(usize, String), Rc<Vec<Fact<'a>>>>>,
+}
+
+/// Something that gives the variable its type between two byte offsets.
+#[derive(Clone)]
+struct Fact<'a> {
+    from: usize,
+    until: usize,
+    /// PHPDoc `@var` wins over the assignment it annotates.
+    priority: u8,
+    source: Source<'a>,
+}
+#[derive(Clone)]
+enum Source<'a> {
+    Types(Vec<String>),
+    /// Right side of an assignment.
+    Expression(Node<'a>),
+    /// Array iterated by `foreach`.
+    Elements(Node<'a>),
+}
+
+impl<'a> TypeInference<'a> {
+    pub fn new(tree: &'a Tree, document: &'a [u8]) -> Self {
+        Self {
+            tree,
+            document,
+            resolver: NameResolver::new(tree, document),
+            use_index: true,
+            facts: RefCell::default(),
+        }
+    }
+    /// Infers types from the document alone: calls and property reads stay untyped.
+    /// Indexers use it as the index is not available on their threads
+    /// and is already borrowed when a file is indexed on the main one.
+    pub fn local(tree: &'a Tree, document: &'a [u8]) -> Self {
+        Self {
+            use_index: false,
+            ..Self::new(tree, document)
+        }
+    }
+    /// Types the variable (with or without `$`) may hold at the point, empty if it is unknown.
+    pub fn variable_types(&self, name: &str, point: Point) -> Vec<String> {
+        match self
+            .tree
+            .root_node()
+            .descendant_for_point_range(point, point)
+        {
+            Some(node) => self.types_at(
+                name.trim_start_matches('$'),
+                &node,
+                byte_offset(self.document, point),
+            ),
+            None => vec![],
+        }
+    }
+    /// Types of the value of the expression, every arm of a union type separately.
+    pub fn expression_types(&self, node: &Node<'a>) -> Vec<String> {
+        let mut types = vec![];
+        for fqn in self.value_types(node) {
+            if !types.contains(&fqn) {
+                types.push(fqn);
+            }
+        }
+        types
+    }
+    fn value_types(&self, node: &Node<'a>) -> Vec<String> {
+        match node.kind() {
+            "variable_name" => match self.variable_name(node) {
+                Some(name) => self.types_at(name, node, node.start_byte()),
+                None => vec![],
+            },
+            "object_creation_expression" => {
+                let mut cursor = node.walk();
+                let class_node = node.named_children(&mut cursor).find(|n| {
+                    matches!(
+                        n.kind(),
+                        "name" | "qualified_name" | "relative_name" | "relative_scope"
+                    )
+                });
+                class_node
+                    .and_then(|n| self.resolve_class_name(n.utf8_text(self.document).ok()?, &n))
+                    .into_iter()
+                    .collect()
+            }
+            "parenthesized_expression" | "clone_expression" => match node.named_child(0) {
+                Some(inner) => self.expression_types(&inner),
+                None => vec![],
+            },
+            "member_call_expression"
+            | "nullsafe_member_call_expression"
+            | "member_access_expression"
+            | "nullsafe_member_access_expression" => {
+                let (object, name) = match (
+                    node.child_by_field_name("object"),
+                    node.child_by_field_name("name"),
+                ) {
+                    (Some(object), Some(name)) => (object, name),
+                    _ => return vec![],
+                };
+                let name = name.utf8_text(self.document).unwrap_or_default();
+                // properties are stored with the `$` to keep them apart from methods
+                let name = if node.kind().ends_with("call_expression") {
+                    name.to_string()
+                } else {
+                    format!("${}", name)
+                };
+                self.members_types(self.expression_types(&object), &name)
+            }
+            "scoped_call_expression" | "scoped_property_access_expression" => {
+                let (scope, name) = match (
+                    node.child_by_field_name("scope"),
+                    node.child_by_field_name("name"),
+                ) {
+                    (Some(scope), Some(name)) => (scope, name),
+                    _ => return vec![],
+                };
+                let name = name.utf8_text(self.document).unwrap_or_default();
+                self.members_types(self.scope_types(&scope), name)
+            }
+            "function_call_expression" if self.use_index => {
+                let function = match node.child_by_field_name("function") {
+                    Some(function) if matches!(function.kind(), "name" | "qualified_name") => {
+                        function
+                    }
+                    _ => return vec![],
+                };
+                let name = function.utf8_text(self.document).unwrap_or_default();
+                let candidates = self
+                    .resolver
+                    .resolve_function(name, &function, self.document);
+                let declaration = DB.with_borrow_mut(|db| {
+                    let db = db.as_mut()?;
+                    candidates.iter().find_map(|fqn| {
+                        db.find_by_fqn(fqn)
+                            .ok()?
+                            .into_iter()
+                            .find(|x| x.kind == ClassRecordKind::Function)
+                    })
+                });
+                declaration.map_or(vec![], |x| declared_types(&x, None))
+            }
+            _ => vec![],
+        }
+    }
+    /// Classes of the scope of `Foo::`, `self::` or `$object::`.
+    pub fn scope_types(&self, scope: &Node<'a>) -> Vec<String> {
+        match scope.kind() {
+            "name" | "qualified_name" | "relative_name" | "relative_scope" => scope
+                .utf8_text(self.document)
+                .ok()
+                .and_then(|class_name| self.resolve_class_name(class_name, scope))
+                .into_iter()
+                .collect(),
+            // `$object::create()` calls on the class of the object
+            _ => self.expression_types(scope),
+        }
+    }
+    /// Types of the member looked up on each class, arrays have no members.
+    fn members_types(&self, classes: Vec<String>, name: &str) -> Vec<String> {
+        if !self.use_index {
+            return vec![];
+        }
+        classes
+            .iter()
+            .filter(|class_fqn| !class_fqn.ends_with("[]"))
+            .flat_map(|class_fqn| {
+                let member = DB
+                    .with_borrow_mut(|db| db.as_mut()?.find_member(class_fqn, name).ok())
+                    .flatten();
+                member.map_or(vec![], |x| declared_types(&x, Some(class_fqn)))
+            })
+            .collect()
+    }
+    /// Resolves a class name used at the node, including `self`, `static` and `parent`.
+    pub fn resolve_class_name(&self, name: &str, node: &Node) -> Option<String> {
+        match name.to_lowercase().as_str() {
+            "self" | "static" | "$this" => get_class_fqn(node, self.document),
+            "parent" => self.parent_class(node),
+            _ => Some(self.resolver.resolve_class(name, node, self.document)),
+        }
+    }
+
+    fn types_at(&self, name: &str, node: &Node<'a>, offset: usize) -> Vec<String> {
+        if name == "this" {
+            return get_class_fqn(node, self.document).into_iter().collect();
+        }
+        let scope = enclosing_scope(node);
+        let latest = self
+            .scope_facts(name, scope)
+            .iter()
+            .filter(|fact| fact.from <= offset && offset < fact.until)
+            .max_by_key(|fact| (fact.from, fact.priority))
+            .cloned();
+        match (latest, scope) {
+            (Some(fact), _) => self.fact_types(fact),
+            (None, Some(scope))
+                if scope.kind() == "arrow_function" || self.captures(&scope, name) =>
+            {
+                self.types_at(name, &scope, scope.start_byte())
+            }
+            _ => vec![],
+        }
+    }
+    fn fact_types(&self, fact: Fact<'a>) -> Vec<String> {
+        match fact.source {
+            Source::Types(types) => types,
+            Source::Expression(node) => self.expression_types(&node),
+            Source::Elements(node) => self
+                .expression_types(&node)
+                .into_iter()
+                .filter_map(|t| t.strip_suffix("[]").map(|x| x.to_string()))
+                .collect(),
+        }
+    }
+    /// Parameters typed by the hint or by `@param` of the docblock, the latter is more precise.
+    fn parameter_facts(&self, name: &str, scope: &Node<'a>, facts: &mut Vec<Fact<'a>>) {
+        let parameters = match scope.child_by_field_name("parameters") {
+            Some(parameters) => parameters,
+            None => return,
+        };
+        let mut cursor = parameters.walk();
+        let parameter = parameters.named_children(&mut cursor).find(|p| {
+            p.child_by_field_name("name")
+                .and_then(|n| self.variable_name(&n))
+                == Some(name)
+        });
+        let parameter = match parameter {
+            Some(parameter) => parameter,
+            None => return,
+        };
+        let docblock = scope
+            .prev_sibling()
+            .filter(|c| c.kind() == "comment")
+            .and_then(|c| c.utf8_text(self.document).ok())
+            .unwrap_or_default();
+        let mut types = doc_tag_type(docblock, "@param", Some(name))
+            .map_or(vec![], |doc_type| self.doc_types(doc_type, scope));
+        if types.is_empty() {
+            types = parameter
+                .child_by_field_name("type")
+                .and_then(|type_node| type_node.utf8_text(self.document).ok())
+                .map_or(vec![], |type_hint| self.doc_types(type_hint, scope));
+        }
+        facts.push(Fact {
+            from: scope.start_byte(),
+            until: usize::MAX,
+            priority: 0,
+            source: Source::Types(types),
+        });
+    }
+    /// Facts of the variable in the whole scope, the global code if there is no scope.
+    /// They are collected on the first lookup and shared by the later ones.
+    fn scope_facts(&self, name: &str, scope: Option<Node<'a>>) -> Rc<Vec<Fact<'a>>> {
+        let root = scope.unwrap_or(self.tree.root_node());
+        let key = (root.id(), name.to_string());
+        if let Some(facts) = self.facts.borrow().get(&key) {
+            return facts.clone();
+        }
+        let mut facts = vec![];
+        if let Some(scope) = scope {
+            self.parameter_facts(name, &scope, &mut facts);
+        }
+        self.collect_facts(name, &root, &mut facts);
+        let facts = Rc::new(facts);
+        self.facts.borrow_mut().insert(key, facts.clone());
+        facts
+    }
+    /// Walks the scope in the source order skipping nested functions and classes.
+    fn collect_facts(&self, name: &str, scope: &Node<'a>, facts: &mut Vec<Fact<'a>>) {
+        let mut stack = vec![*scope];
+        while let Some(node) = stack.pop() {
+            if node != *scope
+                && (SCOPES.contains(&node.kind()) || DECLARATIONS.contains(&node.kind()))
+            {
+                continue;
+            }
+            self.node_facts(name, &node, facts);
+            let mut cursor = node.walk();
+            stack.extend(node.named_children(&mut cursor));
+        }
+    }
+    fn node_facts(&self, name: &str, node: &Node<'a>, facts: &mut Vec<Fact<'a>>) {
+        match node.kind() {
+            "assignment_expression" => {
+                let is_target = node
+                    .child_by_field_name("left")
+                    .is_some_and(|left| self.is_variable(&left, name));
+                if let (true, Some(right)) = (is_target, node.child_by_field_name("right")) {
+                    // the right side still sees the previous value
+                    facts.push(Fact {
+                        from: node.end_byte(),
+                        until: usize::MAX,
+                        priority: 0,
+                        source: Source::Expression(right),
+                    });
+                }
+            }
+            "catch_clause" => {
+                let is_target = node
+                    .child_by_field_name("name")
+                    .is_some_and(|n| self.is_variable(&n, name));
+                let type_list = node
+                    .child_by_field_name("type")
+                    .and_then(|type_list| type_list.utf8_text(self.document).ok());
+                if let (true, Some(type_list)) = (is_target, type_list) {
+                    facts.push(Fact {
+                        from: node.start_byte(),
+                        until: usize::MAX,
+                        priority: 0,
+                        source: Source::Types(self.doc_types(type_list, node)),
+                    });
+                }
+            }
+            "foreach_statement" => {
+                let (iterated, value) = match (node.named_child(0), node.named_child(1)) {
+                    (Some(iterated), Some(value)) => (iterated, value),
+                    _ => return,
+                };
+                // `$key => $value` and `&$value`
+                let value = match value.kind() {
+                    "pair" => value.named_child(value.named_child_count().saturating_sub(1)),
+                    _ => Some(value),
+                };
+                let value = value.map(|v| match v.kind() {
+                    "by_ref" => v.named_child(0).unwrap_or(v),
+                    _ => v,
+                });
+                if value.is_some_and(|v| self.is_variable(&v, name)) {
+                    facts.push(Fact {
+                        from: value.unwrap().end_byte(),
+                        until: usize::MAX,
+                        priority: 0,
+                        source: Source::Elements(iterated),
+                    });
+                }
+            }
+            "if_statement" | "else_if_clause" | "while_statement" => {
+                let (condition, body) = match (
+                    node.child_by_field_name("condition"),
+                    node.child_by_field_name("body"),
+                ) {
+                    (Some(condition), Some(body)) => (condition, body),
+                    _ => return,
+                };
+                let types = self.instanceof_types(&condition, name);
+                if !types.is_empty() {
+                    facts.push(Fact {
+                        from: body.start_byte(),
+                        until: body.end_byte(),
+                        priority: 0,
+                        source: Source::Types(types),
+                    });
+                }
+            }
+            "comment" => self.comment_facts(name, node, facts),
+            _ => (),
+        }
+    }
+    /// `/** @var Foo $name */` types the variable from there on,
+    /// `/** @var Foo */` types the assignment right after the comment.
+    fn comment_facts(&self, name: &str, comment: &Node<'a>, facts: &mut Vec<Fact<'a>>) {
+        let text = comment.utf8_text(self.document).unwrap_or_default();
+        if let Some(doc_type) = doc_tag_type(text, "@var", Some(name)) {
+            facts.push(Fact {
+                from: comment.end_byte(),
+                until: usize::MAX,
+                priority: 1,
+                source: Source::Types(self.doc_types(doc_type, comment)),
+            });
+            return;
+        }
+        let assignment = comment
+            .next_named_sibling()
+            .filter(|n| n.kind() == "expression_statement")
+            .and_then(|n| n.named_child(0))
+            .filter(|n| n.kind() == "assignment_expression");
+        let assignment = match assignment {
+            Some(assignment) => assignment,
+            None => return,
+        };
+        let is_target = assignment
+            .child_by_field_name("left")
+            .is_some_and(|left| self.is_variable(&left, name));
+        if let (true, Some(doc_type)) = (is_target, doc_tag_type(text, "@var", None)) {
+            facts.push(Fact {
+                from: assignment.end_byte(),
+                until: usize::MAX,
+                priority: 1,
+                source: Source::Types(self.doc_types(doc_type, comment)),
+            });
+        }
+    }
+    /// Classes the condition checks the variable against with `instanceof`,
+    /// also inside `&&` conditions. Negations do not narrow the type.
+    fn instanceof_types(&self, condition: &Node, name: &str) -> Vec<String> {
+        match condition.kind() {
+            "parenthesized_expression" => match condition.named_child(0) {
+                Some(inner) => self.instanceof_types(&inner, name),
+                None => vec![],
+            },
+            "binary_expression" => {
+                let operator = condition
+                    .child_by_field_name("operator")
+                    .map(|operator| operator.kind().to_lowercase())
+                    .unwrap_or_default();
+                let (left, right) = match (
+                    condition.child_by_field_name("left"),
+                    condition.child_by_field_name("right"),
+                ) {
+                    (Some(left), Some(right)) => (left, right),
+                    _ => return vec![],
+                };
+                match operator.as_str() {
+                    "instanceof" if self.is_variable(&left, name) => right
+                        .utf8_text(self.document)
+                        .ok()
+                        .and_then(|class_name| self.resolve_class_name(class_name, &right))
+                        .into_iter()
+                        .collect(),
+                    "&&" | "and" => {
+                        let mut types = self.instanceof_types(&left, name);
+                        types.extend(self.instanceof_types(&right, name));
+                        types
+                    }
+                    _ => vec![],
+                }
+            }
+            _ => vec![],
+        }
+    }
+    /// Class names of a type hint or a PHPDoc type used at the node.
+    fn doc_types(&self, doc_type: &str, node: &Node) -> Vec<String> {
+        class_types(doc_type, |name| self.resolve_class_name(name, node))
+    }
+    /// Parent of the class enclosing the node, read from its `extends` clause.
+    fn parent_class(&self, node: &Node) -> Option<String> {
+        let mut current = node.parent();
+        while let Some(class_node) = current {
+            if class_node.kind() == "class_declaration" {
+                let mut cursor = class_node.walk();
+                let base_clause = class_node
+                    .named_children(&mut cursor)
+                    .find(|n| n.kind() == "base_clause")?;
+                let mut cursor = base_clause.walk();
+                let parent = base_clause
+                    .named_children(&mut cursor)
+                    .find(|n| matches!(n.kind(), "name" | "qualified_name"))?;
+                let name = parent.utf8_text(self.document).ok()?;
+                return Some(self.resolver.resolve_class(name, &parent, self.document));
+            }
+            current = class_node.parent();
+        }
+        None
+    }
+    /// Checks whether the closure imports the variable with `use`.
+    fn captures(&self, closure: &Node, name: &str) -> bool {
+        let mut cursor = closure.walk();
+        let use_clause = closure
+            .named_children(&mut cursor)
+            .find(|n| n.kind() == "anonymous_function_use_clause");
+        let use_clause = match use_clause {
+            Some(use_clause) => use_clause,
+            None => return false,
+        };
+        let mut cursor = use_clause.walk();
+        let captures = use_clause.named_children(&mut cursor).any(|n| {
+            let n = match n.kind() {
+                "by_ref" => n.named_child(0).unwrap_or(n),
+                _ => n,
+            };
+            self.is_variable(&n, name)
+        });
+        captures
+    }
+    fn variable_name(&self, node: &Node) -> Option<&'a str> {
+        if node.kind() != "variable_name" {
+            return None;
+        }
+        node.named_child(0)?.utf8_text(self.document).ok()
+    }
+    fn is_variable(&self, node: &Node, name: &str) -> bool {
+        self.variable_name(node) == Some(name)
+    }
+}
+
+/// Types the declaration says it returns or holds. `static` and `$this` are the class
+/// the member was looked up on, `self` and `parent` are relative to the declaring class.
+fn declared_types(declaration: &ClassRecord, receiver: Option<&str>) -> Vec<String> {
+    let declaring_class = declaration.fqn.rsplit_once("::").map(|(class, _)| class);
+    let type_fqn = match &declaration.type_fqn {
+        Some(type_fqn) => type_fqn,
+        None => return vec![],
+    };
+    type_fqn
+        .split('|')
+        .filter_map(|arm| {
+            let (fqn, suffix) = match arm.strip_suffix("[]") {
+                Some(element) => (element, "[]"),
+                None => (arm, ""),
+            };
+            let fqn = match fqn {
+                "self" => declaring_class?.to_string(),
+                "static" | "$this" => receiver.or(declaring_class)?.to_string(),
+                "parent" => DB.with_borrow_mut(|db| {
+                    let supertypes = db.as_mut()?.find_supertypes(declaring_class?).ok()?;
+                    supertypes
+                        .into_iter()
+                        .find(|x| x.kind == InheritanceKind::Extends)
+                        .map(|x| x.parent_fqn)
+                })?,
+                _ => fqn.to_string(),
+            };
+            Some(format!("{}{}", fqn, suffix))
+        })
+        .collect()
+}
+
+fn enclosing_scope<'a>(node: &Node<'a>) -> Option<Node<'a>> {
+    let mut current = node.parent();
+    while let Some(node) = current {
+        if SCOPES.contains(&node.kind()) {
+            return Some(node);
+        }
+        current = node.parent();
+    }
+    None
+}
+
+/// Columns of points are bytes in the line.
+fn byte_offset(document: &[u8], point: Point) -> usize {
+    let line_start: usize = document
+        .split_inclusive(|&c| c == b'\n')
+        .take(point.row)
+        .map(|line| line.len())
+        .sum();
+    (line_start + point.column).min(document.len())
+}
+
+/// Returns the type of the first `@param Type $name` or `@var Type [$name]` tag of the docblock.
+pub fn doc_tag_type<'d>(docblock: &'d str, tag: &str, name: Option<&str>) -> Option<&'d str> {
+    for line in docblock.lines() {
+        let rest = match line.split_once(tag) {
+            Some((_, rest)) if rest.starts_with(char::is_whitespace) => rest.trim_start(),
+            _ => continue,
+        };
+        // generic types may contain spaces: `array<int, Foo>`
+        let mut depth = 0;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '<' | '{' | '(' => depth += 1,
+                    '>' | '}' | ')' => depth -= 1,
+                    _ => (),
+                }
+                depth <= 0 && c.is_whitespace()
+            })
+            .map_or(rest.len(), |(index, _)| index);
+        let (doc_type, after) = rest.split_at(end);
+        let variable = after
+            .split_whitespace()
+            .next()
+            .filter(|x| x.starts_with('$'))
+            .map(|x| x.trim_start_matches('$'));
+        match (name, variable) {
+            (Some(name), Some(variable)) if name == variable => return Some(doc_type),
+            (None, None) => return Some(doc_type),
+            _ => continue,
+        }
+    }
+    None
+}
+
+/// Class names of a type: `?Foo`, `Foo|Bar|null`, `(A&B)|null` as well as PHPDoc `Foo[]`,
+/// `array<int, Foo>`, `list<Foo>` (arrays of `Foo`) and `Collection<Foo>` (the collection itself).
+/// Scalar types are skipped, `resolve` maps the names to FQNs.
+pub fn class_types<F: Fn(&str) -> Option<String>>(type_text: &str, resolve: F) -> Vec<String> {
+    split_top_level(type_text, '|')
+        .into_iter()
+        .flat_map(|arm| {
+            let arm = arm.trim().trim_start_matches('(').trim_end_matches(')');
+            split_top_level(arm, '&')
+        })
+        .filter_map(|arm| {
+            let arm = arm.trim().trim_start_matches('?');
+            let (class_name, is_array) = if let Some(element) = arm.strip_suffix("[]") {
+                (element, true)
+            } else if let Some((generic, arguments)) = arm.split_once('<') {
+                match generic.to_lowercase().as_str() {
+                    "array" | "list" | "iterable" | "non-empty-array" | "non-empty-list" => {
+                        let arguments = arguments.strip_suffix('>').unwrap_or(arguments);
+                        let element = split_top_level(arguments, ',').pop()?.trim();
+                        (element.split('<').next()?, true)
+                    }
+                    _ => (generic, false),
+                }
+            } else {
+                (arm, false)
+            };
+            if is_scalar(class_name) {
+                return None;
+            }
+            let fqn = resolve(class_name)?;
+            Some(if is_array { format!("{}[]", fqn) } else { fqn })
+        })
+        .collect()
+}
+
+/// Splits the type on the separator outside of `<...>`.
+fn split_top_level(doc_type: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in doc_type.char_indices() {
+        match c {
+            '<' | '{' | '(' => depth += 1,
+            '>' | '}' | ')' => depth -= 1,
+            _ if c == separator && depth == 0 => {
+                parts.push(&doc_type[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => (),
+        }
+    }
+    parts.push(&doc_type[start..]);
+    parts
+}
+
+fn is_scalar(name: &str) -> bool {
+    let name = name.to_lowercase();
+    // PHPStan pseudo types: `class-string`, `non-empty-string`, `positive-int`...
+    name.is_empty()
+        || name.contains('-')
+        || matches!(
+            name.as_str(),
+            "int"
+                | "integer"
+                | "float"
+                | "double"
+                | "string"
+                | "bool"
+                | "boolean"
+                | "true"
+                | "false"
+                | "null"
+                | "void"
+                | "never"
+                | "mixed"
+                | "array"
+                | "list"
+                | "iterable"
+                | "callable"
+                | "object"
+                | "resource"
+                | "scalar"
+                | "numeric"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::parse;
+
+    #[test]
+    fn doc_tag_type_finds_the_named_tag() {
+        let docblock = "/**\n * @param int $id\n * @param array<int, Foo> $items\n */";
+        assert_eq!(doc_tag_type(docblock, "@param", Some("id")), Some("int"));
+        assert_eq!(
+            doc_tag_type(docblock, "@param", Some("items")),
+            Some("array<int, Foo>")
+        );
+        assert_eq!(doc_tag_type(docblock, "@param", Some("other")), None);
+        assert_eq!(doc_tag_type(docblock, "@return", None), None);
+    }
+
+    #[test]
+    fn doc_tag_type_tells_named_and_unnamed_vars_apart() {
+        assert_eq!(doc_tag_type("/** @var Foo */", "@var", None), Some("Foo"));
+        assert_eq!(doc_tag_type("/** @var Foo $foo */", "@var", None), None);
+        assert_eq!(
+            doc_tag_type("/** @var Foo $foo */", "@var", Some("foo")),
+            Some("Foo")
+        );
+        assert_eq!(doc_tag_type("/** @variable Foo */", "@var", None), None);
+    }
+
+    #[test]
+    fn class_types_skip_scalars_and_mark_arrays() {
+        let types = |type_text| class_types(type_text, |name| Some(format!("App\\{}", name)));
+        assert_eq!(types("?Foo"), ["App\\Foo"]);
+        assert_eq!(types("Foo|Bar|null"), ["App\\Foo", "App\\Bar"]);
+        assert_eq!(types("(A&B)|null"), ["App\\A", "App\\B"]);
+        assert_eq!(types("Foo[]"), ["App\\Foo[]"]);
+        assert_eq!(types("array<int, Foo>"), ["App\\Foo[]"]);
+        assert_eq!(types("list<Foo>|false"), ["App\\Foo[]"]);
+        assert_eq!(types("Collection<Foo>"), ["App\\Collection"]);
+        assert!(types("int|string|class-string").is_empty());
+    }
+
+    const SOURCE: &str = r#"<?php
+namespace App;
+
+use Lib\Logger;
+
+class Service {
+    public function run(Logger $logger, $id) {
+        $user = new User();
+        $logger->log();
+        $closure = function () use ($user) {
+            $inner = $user;
+        };
+        $arrow = fn() => $user;
+        $other = function () {
+            $missing = $user;
+        };
+        if ($id instanceof Order) {
+            $narrowed = $id;
+        }
+        $user = new Admin();
+        $last = $user;
+    }
+}
+"#;
+
+    /// Types of the last variable in the needle.
+    fn types_at(needle: &str) -> Vec<String> {
+        let tree = parse(SOURCE.as_bytes()).unwrap();
+        let inference = TypeInference::local(&tree, SOURCE.as_bytes());
+        types_with(&inference, needle)
+    }
+    fn types_with(inference: &TypeInference, needle: &str) -> Vec<String> {
+        let offset = SOURCE.find(needle).unwrap() + needle.rfind('$').unwrap();
+        let row = SOURCE[..offset].matches('\n').count();
+        let column = offset - SOURCE[..offset].rfind('\n').unwrap() - 1;
+        let name = SOURCE[offset..]
+            .split(|c: char| !c.is_alphanumeric() && c != '$' && c != '_')
+            .next()
+            .unwrap();
+        inference.variable_types(name, Point::new(row, column))
+    }
+
+    #[test]
+    fn variables_are_typed_by_their_scope() {
+        assert_eq!(types_at("$logger->log"), ["Lib\\Logger"]);
+        assert_eq!(types_at("$last = $user"), ["App\\Admin"]);
+        assert_eq!(types_at("$narrowed = $id"), ["App\\Order"]);
+    }
+
+    #[test]
+    fn closures_see_only_captured_variables() {
+        assert_eq!(types_at("$inner = $user"), ["App\\User"]);
+        assert_eq!(types_at("=> $user"), ["App\\User"]);
+        assert!(types_at("$missing = $user").is_empty());
+    }
+
+    #[test]
+    fn facts_of_a_scope_are_shared_by_lookups() {
+        let tree = parse(SOURCE.as_bytes()).unwrap();
+        let inference = TypeInference::local(&tree, SOURCE.as_bytes());
+        assert_eq!(types_with(&inference, "$last = $user"), ["App\\Admin"]);
+        assert_eq!(types_with(&inference, "=> $user"), ["App\\User"]);
+        assert_eq!(types_with(&inference, "$inner = $user"), ["App\\User"]);
+        assert_eq!(types_with(&inference, "$last = $user"), ["App\\Admin"]);
+    }
+}
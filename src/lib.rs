@@ -1,21 +1,30 @@
+pub mod call_hierarchy;
+pub mod composer;
+pub mod config;
 pub mod db;
+pub mod document;
 pub mod indexer;
+pub mod inference;
+pub mod outline;
+pub mod search;
 pub mod utils;
 
 use std::{cell::RefCell, str::FromStr};
 
-use db::Db;
+use db::{ClassRecordKind, Db};
+use indexer::Folder;
 use lsp_types::{InitializeParams, Location, Position, Range, Url};
 use sqlx::{sqlite::SqliteRow, Row};
 use tree_sitter::Node;
 thread_local! {
 
     pub static DB: RefCell<Option<Db>> = RefCell::new(None);
+    pub static FOLDERS: RefCell<Vec<Folder>> = RefCell::new(vec![]);
 }
 
 #[derive(Debug)]
 pub struct Symbol {
-    pub kind: SymbolKind,
+    pub kind: ClassRecordKind,
     pub name: String,
     pub location: Location,
 }
@@ -36,23 +45,13 @@ impl sqlx::FromRow<'_, SqliteRow> for Symbol {
         let location: Location = Location::new(uri, range);
 
         Ok(Self {
-            kind: Default::default(),
+            kind: row.try_get("kind")?,
             name: row.try_get("name")?,
             location,
         })
     }
 }
 
-#[derive(Debug, Default)]
-#[repr(u8)]
-pub enum SymbolKind {
-    #[default]
-    Global = 0,
-    Class,
-    Method,
-    Function,
-    Variable,
-}
 pub fn get_node_name(node: &Node, document: &[u8]) -> Option<String> {
     for i in 0..node.child_count() {
         if let Some(child) = node.child(i) {
@@ -77,6 +76,8 @@ pub fn debug_node<'a>(node: &'a tree_sitter::Node<'a>, lvl: usize) {
 
 pub trait ParamsGetProjectPath {
     fn get_project_path(&self) -> anyhow::Result<std::path::PathBuf>;
+    /// Paths of all workspace folders, or of the root if the client does not send folders.
+    fn get_project_paths(&self) -> anyhow::Result<Vec<std::path::PathBuf>>;
 }
 impl ParamsGetProjectPath for InitializeParams {
     fn get_project_path(&self) -> anyhow::Result<std::path::PathBuf> {
@@ -89,6 +90,20 @@ impl ParamsGetProjectPath for InitializeParams {
 
         Ok(root_path)
     }
+    fn get_project_paths(&self) -> anyhow::Result<Vec<std::path::PathBuf>> {
+        match &self.workspace_folders {
+            Some(folders) if !folders.is_empty() => folders
+                .iter()
+                .map(|folder| {
+                    folder
+                        .uri
+                        .to_file_path()
+                        .map_err(|_| anyhow::anyhow!("Invalid workspace folder URI {}", folder.uri))
+                })
+                .collect(),
+            _ => Ok(vec![self.get_project_path()?]),
+        }
+    }
 }
 pub trait ToLocation {
     fn to_locaton(&self, url: &Url)-> Location;
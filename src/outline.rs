@@ -0,0 +1,114 @@
+use lsp_types::{DocumentSymbol, Range, SymbolKind, Url};
+use tree_sitter::{Node, Tree};
+
+use crate::{db::ClassRecord, indexer::index_declarations, ToLocation};
+
+/// Builds the outline of the document: namespaces, class-like declarations and functions,
+/// then their members. Declarations come from the declaration indexers, so the outline
+/// shows the same symbols the index knows about.
+pub fn document_symbols(contents: &[u8], tree: &Tree, uri: &Url) -> Vec<DocumentSymbol> {
+    let file = index_declarations(contents, tree, uri);
+    let mut symbols = namespaces(contents, tree, uri);
+    symbols.extend(file.declarations.iter().map(to_document_symbol));
+    nest(symbols)
+}
+
+#[allow(deprecated)]
+fn to_document_symbol(record: &ClassRecord) -> DocumentSymbol {
+    let detail = record.parameters.as_ref().map(|params| {
+        format!(
+            "{}{}",
+            params,
+            record
+                .return_type
+                .as_ref()
+                .map_or("".to_string(), |rt| format!(": {}", rt))
+        )
+    });
+    DocumentSymbol {
+        name: record.name().to_string(),
+        detail,
+        kind: record.kind.symbol_kind(),
+        tags: None,
+        deprecated: None,
+        range: record.range,
+        selection_range: record.location.range,
+        children: None,
+    }
+}
+
+/// Both `namespace Foo { ... }` and `namespace Foo;`, the latter spans until the next
+/// namespace or the end of the file.
+#[allow(deprecated)]
+fn namespaces(contents: &[u8], tree: &Tree, uri: &Url) -> Vec<DocumentSymbol> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let definitions: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "namespace_definition")
+        .collect();
+    let mut symbols = vec![];
+    for (index, definition) in definitions.iter().enumerate() {
+        let name_node = match definition.child_by_field_name("name") {
+            Some(name_node) => name_node,
+            None => continue,
+        };
+        let mut range = definition.range().to_locaton(uri).range;
+        if definition.child_by_field_name("body").is_none() {
+            range.end = match definitions.get(index + 1) {
+                Some(next) => next.range().to_locaton(uri).range.start,
+                None => root.range().to_locaton(uri).range.end,
+            };
+        }
+        symbols.push(DocumentSymbol {
+            name: name_node
+                .utf8_text(contents)
+                .unwrap_or_default()
+                .to_string(),
+            detail: None,
+            kind: SymbolKind::NAMESPACE,
+            tags: None,
+            deprecated: None,
+            range,
+            selection_range: name_node.range().to_locaton(uri).range,
+            children: None,
+        });
+    }
+    symbols
+}
+
+/// Puts every symbol into the innermost symbol whose range contains it.
+fn nest(mut symbols: Vec<DocumentSymbol>) -> Vec<DocumentSymbol> {
+    // parents go before their children: by start, then the widest first
+    symbols.sort_by(|a, b| {
+        a.range
+            .start
+            .cmp(&b.range.start)
+            .then(b.range.end.cmp(&a.range.end))
+    });
+    let mut roots = vec![];
+    let mut stack: Vec<DocumentSymbol> = vec![];
+    for symbol in symbols {
+        while let Some(parent) = stack.last() {
+            if contains(&parent.range, &symbol.range) {
+                break;
+            }
+            let done = stack.pop().unwrap();
+            attach(&mut stack, &mut roots, done);
+        }
+        stack.push(symbol);
+    }
+    while let Some(done) = stack.pop() {
+        attach(&mut stack, &mut roots, done);
+    }
+    roots
+}
+fn attach(stack: &mut [DocumentSymbol], roots: &mut Vec<DocumentSymbol>, symbol: DocumentSymbol) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+        None => roots.push(symbol),
+    }
+}
+fn contains(outer: &Range, inner: &Range) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
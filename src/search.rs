@@ -0,0 +1,110 @@
+/// Splits a name into camel-case humps: `UserCommentController` gives `User`, `Comment`,
+/// `Controller`; underscores and digits separate humps too.
+pub fn humps(name: &str) -> Vec<&str> {
+    let mut humps = vec![];
+    let mut start = None;
+    let mut prev_lower = false;
+    for (index, c) in name.char_indices() {
+        if !c.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                humps.push(&name[start..index]);
+            }
+            prev_lower = false;
+            continue;
+        }
+        let boundary = c.is_uppercase() && prev_lower;
+        if boundary {
+            if let Some(start) = start {
+                humps.push(&name[start..index]);
+            }
+            start = Some(index);
+        } else if start.is_none() {
+            start = Some(index);
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+    }
+    if let Some(start) = start {
+        humps.push(&name[start..]);
+    }
+    humps
+}
+
+/// Ranks how well the short name matches the query, the lower the better:
+/// exact match, prefix, camel humps (`UsCoCtl` for `UserCommentController`),
+/// substring and finally characters of the query in the same order.
+pub fn score(query: &str, name: &str) -> Option<u8> {
+    let query_lower = query.to_lowercase();
+    let name_lower = name.to_lowercase();
+    if name_lower == query_lower {
+        Some(0)
+    } else if name_lower.starts_with(&query_lower) {
+        Some(1)
+    } else if matches_humps(&humps(query), &humps(name)) {
+        Some(2)
+    } else if name_lower.contains(&query_lower) {
+        Some(3)
+    } else if is_subsequence(&query_lower, &name_lower) {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// Every hump of the query has to start a hump of the name, in the same order,
+/// with the rest of its characters found in that hump.
+fn matches_humps(query: &[&str], name: &[&str]) -> bool {
+    if query.len() < 2 {
+        return false;
+    }
+    let mut name = name.iter();
+    query.iter().all(|query_hump| {
+        let query_hump = query_hump.to_lowercase();
+        name.any(|name_hump| {
+            let name_hump = name_hump.to_lowercase();
+            query_hump.chars().next() == name_hump.chars().next()
+                && is_subsequence(&query_hump, &name_hump)
+        })
+    })
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|x| x == c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn humps_split_camel_case_underscores_and_digits() {
+        assert_eq!(
+            humps("UserCommentController"),
+            ["User", "Comment", "Controller"]
+        );
+        assert_eq!(humps("get_user_by_id"), ["get", "user", "by", "id"]);
+        assert_eq!(humps("HTTPClient2Factory"), ["HTTPClient2", "Factory"]);
+        assert_eq!(humps("__construct"), ["construct"]);
+        assert!(humps("").is_empty());
+    }
+
+    #[test]
+    fn score_ranks_match_kinds() {
+        assert_eq!(
+            score("usercommentcontroller", "UserCommentController"),
+            Some(0)
+        );
+        assert_eq!(score("userC", "UserCommentController"), Some(1));
+        assert_eq!(score("UsCoCtl", "UserCommentController"), Some(2));
+        assert_eq!(score("comment", "UserCommentController"), Some(3));
+        assert_eq!(score("ucc", "UserCommentController"), Some(4));
+        assert_eq!(score("ccu", "UserCommentController"), None);
+    }
+
+    #[test]
+    fn humps_of_query_keep_their_order() {
+        assert_eq!(score("CoUs", "UserCommentController"), None);
+        // a single hump is a prefix or substring match, not a hump one
+        assert_eq!(score("Ctl", "UserCommentController"), Some(4));
+    }
+}